@@ -1,9 +1,122 @@
 
 use super::*;
-use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
-use gpu_allocator::AllocatorDebugSettings;
-use gpu_allocator::vulkan::{Allocator, AllocatorCreateDesc};
-use raw_window_handle::HasRawWindowHandle;
-use std::sync::Arc;
+use ash::vk;
 
+impl Adapter{
+    pub fn properties(&self) -> vk::PhysicalDeviceProperties{
+        unsafe{
+            self.instance.raw.get_physical_device_properties(self.pdevice)
+        }
+    }
+
+    pub fn supports_extension(&self, name: &CStr) -> bool{
+        unsafe{
+            self.instance.raw
+                .enumerate_device_extension_properties(self.pdevice)
+                .unwrap()
+                .iter()
+                .any(|extension| CStr::from_ptr(extension.extension_name.as_ptr()) == name)
+        }
+    }
+
+    /// Sample counts usable for both color and depth framebuffer attachments.
+    pub fn supported_sample_counts(&self) -> vk::SampleCountFlags{
+        let limits = self.properties().limits;
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+    }
+
+    /// Highest supported sample count that does not exceed `max`.
+    pub fn max_sample_count(&self, max: vk::SampleCountFlags) -> vk::SampleCountFlags{
+        let supported = self.supported_sample_counts();
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+            .iter()
+            .copied()
+            .filter(|count| count.as_raw() <= max.as_raw())
+            .find(|count| supported.contains(*count))
+            .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties{
+        unsafe{
+            self.instance.raw.get_physical_device_format_properties(self.pdevice, format)
+        }
+    }
+
+    pub fn supports(&self, format: vk::Format, usage: vk::ImageUsageFlags, tiling: vk::ImageTiling) -> bool{
+        let properties = self.format_properties(format);
+        let features = match tiling{
+            vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+            _ => properties.optimal_tiling_features,
+        };
+        features.contains(format_features_from_usage(usage))
+    }
+
+    pub fn first_supported_format(
+        &self,
+        candidates: &[vk::Format],
+        usage: vk::ImageUsageFlags,
+        tiling: vk::ImageTiling,
+    ) -> Option<vk::Format>{
+        candidates
+            .iter()
+            .copied()
+            .find(|format| self.supports(*format, usage, tiling))
+    }
+
+    pub fn first_supported_color_format(&self, candidates: &[vk::Format]) -> Option<vk::Format>{
+        self.first_supported_format(
+            candidates,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageTiling::OPTIMAL,
+        )
+    }
+
+    pub fn first_supported_storage_format(&self, candidates: &[vk::Format]) -> Option<vk::Format>{
+        self.first_supported_format(
+            candidates,
+            vk::ImageUsageFlags::STORAGE,
+            vk::ImageTiling::OPTIMAL,
+        )
+    }
+
+    pub fn best_depth_format(&self, stencil: bool) -> vk::Format{
+        let candidates: &[vk::Format] = match stencil{
+            true => &[
+                vk::Format::D32_SFLOAT_S8_UINT,
+                vk::Format::D24_UNORM_S8_UINT,
+                vk::Format::D16_UNORM_S8_UINT,
+            ],
+            false => &[
+                vk::Format::D32_SFLOAT,
+                vk::Format::X8_D24_UNORM_PACK32,
+                vk::Format::D16_UNORM,
+            ],
+        };
+        self.first_supported_format(
+            candidates,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageTiling::OPTIMAL,
+        ).expect("Could not find a supported depth format")
+    }
+}
+
+pub fn format_features_from_usage(usage: vk::ImageUsageFlags) -> vk::FormatFeatureFlags{
+    [
+        (vk::ImageUsageFlags::TRANSFER_SRC, vk::FormatFeatureFlags::TRANSFER_SRC),
+        (vk::ImageUsageFlags::TRANSFER_DST, vk::FormatFeatureFlags::TRANSFER_DST),
+        (vk::ImageUsageFlags::SAMPLED, vk::FormatFeatureFlags::SAMPLED_IMAGE),
+        (vk::ImageUsageFlags::STORAGE, vk::FormatFeatureFlags::STORAGE_IMAGE),
+        (vk::ImageUsageFlags::COLOR_ATTACHMENT, vk::FormatFeatureFlags::COLOR_ATTACHMENT),
+        (vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+    ]
+        .iter()
+        .filter(|(image_usage, _)| usage.contains(*image_usage))
+        .fold(vk::FormatFeatureFlags::empty(), |features, (_, feature)| features | *feature)
+}
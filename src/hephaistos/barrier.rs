@@ -1,76 +1,420 @@
 
 use ash::vk;
-use ash::extensions::{khr, ext};
+use ash::extensions::khr;
 use vk_sync::AccessType;
 use super::*;
 
-pub struct ImageBarrier {
-    image: vk::Image,
-    prev_access: vk_sync::AccessType,
-    next_access: vk_sync::AccessType,
-    aspect_mask: vk::ImageAspectFlags,
-    discard: bool,
-}
-
-pub fn record_image_barrier(device: &SharedDevice, cb: vk::CommandBuffer, barrier: ImageBarrier, create_info: vk::ImageViewCreateInfo){
-    let range = vk::ImageSubresourceRange {
-        aspect_mask: barrier.aspect_mask,
-        base_mip_level: 0,
-        level_count: vk::REMAINING_MIP_LEVELS,
-        base_array_layer: 0,
-        layer_count: vk::REMAINING_ARRAY_LAYERS,
-    };
-    let layout_transition_barriers = vk::ImageMemoryBarrier::builder()
-        .image(barrier.image)
-        .dst_access_mask(
-            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-        )
-        .new_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-        .old_layout(vk::ImageLayout::UNDEFINED)
-        .subresource_range(
-            vk::ImageSubresourceRange::builder()
-            .aspect_mask(vk::ImageAspectFlags::DEPTH)
-            .layer_count(1)
-            .build(),
-        );
+pub struct GlobalBarrier<'a> {
+    pub prev_accesses: &'a [AccessType],
+    pub next_accesses: &'a [AccessType],
+}
 
-    unsafe{
-        device.cmd_pipeline_barrier(
-            cb,
-            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-            vk::PipelineStageFlags::TOP_OF_PIPE,
-            vk::DependencyFlags::empty(),
-            &[],
-            &[],
-            &[layout_transition_barriers.build()],
-        );
+pub struct BufferBarrier<'a> {
+    pub buffer: vk::Buffer,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    pub prev_accesses: &'a [AccessType],
+    pub next_accesses: &'a [AccessType],
+}
+
+pub struct ImageBarrier<'a> {
+    pub image: vk::Image,
+    pub prev_accesses: &'a [AccessType],
+    pub next_accesses: &'a [AccessType],
+    pub range: vk::ImageSubresourceRange,
+    pub discard: bool,
+}
+
+impl<'a> GlobalBarrier<'a> {
+    pub fn new(prev_accesses: &'a [AccessType], next_accesses: &'a [AccessType]) -> Self {
+        Self {
+            prev_accesses,
+            next_accesses,
+        }
     }
+}
 
+impl<'a> BufferBarrier<'a> {
+    pub fn new(
+        buffer: vk::Buffer,
+        prev_accesses: &'a [AccessType],
+        next_accesses: &'a [AccessType],
+    ) -> Self {
+        Self {
+            buffer,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            prev_accesses,
+            next_accesses,
+        }
+    }
+
+    pub fn with_range(mut self, offset: vk::DeviceSize, size: vk::DeviceSize) -> Self {
+        self.offset = offset;
+        self.size = size;
+        self
+    }
 }
 
-impl ImageBarrier {
+impl<'a> ImageBarrier<'a> {
     pub fn new(
         image: vk::Image,
-        prev_access: vk_sync::AccessType,
-        next_access: vk_sync::AccessType,
+        prev_accesses: &'a [AccessType],
+        next_accesses: &'a [AccessType],
         aspect_mask: vk::ImageAspectFlags,
     ) -> Self {
         Self {
             image,
-            prev_access,
-            next_access,
+            prev_accesses,
+            next_accesses,
+            range: vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: vk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            },
             discard: false,
-            aspect_mask,
         }
     }
 
+    pub fn with_range(mut self, range: vk::ImageSubresourceRange) -> Self {
+        self.range = range;
+        self
+    }
+
     pub fn with_discard(mut self, discard: bool) -> Self {
         self.discard = discard;
         self
     }
 }
 
+// Only writes have to be made available, reads need an execution dependency at most.
+pub fn src_access_mask(prev_accesses: &[AccessType]) -> vk::AccessFlags {
+    prev_accesses
+        .iter()
+        .filter(|access| is_write_access(**access))
+        .fold(vk::AccessFlags::empty(), |mask, access| {
+            mask | get_access_info(*access).access_mask
+        })
+}
+
+pub fn dst_access_mask(next_accesses: &[AccessType]) -> vk::AccessFlags {
+    next_accesses
+        .iter()
+        .fold(vk::AccessFlags::empty(), |mask, access| {
+            mask | get_access_info(*access).access_mask
+        })
+}
+
+pub fn stage_mask(accesses: &[AccessType]) -> vk::PipelineStageFlags {
+    accesses
+        .iter()
+        .fold(vk::PipelineStageFlags::empty(), |mask, access| {
+            mask | get_access_info(*access).stage_mask
+        })
+}
+
+// Accesses requiring different layouts have to share GENERAL.
+pub fn image_layout(accesses: &[AccessType]) -> vk::ImageLayout {
+    let mut layouts = accesses
+        .iter()
+        .map(|access| get_access_info(*access).image_layout);
+
+    match layouts.next() {
+        Some(layout) if layouts.all(|other| other == layout) => layout,
+        Some(_) => vk::ImageLayout::GENERAL,
+        None => vk::ImageLayout::UNDEFINED,
+    }
+}
+
+pub fn get_memory_barrier(barrier: &GlobalBarrier) -> vk::MemoryBarrier {
+    vk::MemoryBarrier {
+        src_access_mask: src_access_mask(barrier.prev_accesses),
+        dst_access_mask: dst_access_mask(barrier.next_accesses),
+        ..Default::default()
+    }
+}
+
+pub fn get_buffer_memory_barrier(barrier: &BufferBarrier) -> vk::BufferMemoryBarrier {
+    vk::BufferMemoryBarrier {
+        src_access_mask: src_access_mask(barrier.prev_accesses),
+        dst_access_mask: dst_access_mask(barrier.next_accesses),
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        buffer: barrier.buffer,
+        offset: barrier.offset,
+        size: barrier.size,
+        ..Default::default()
+    }
+}
+
+pub fn get_image_memory_barrier(barrier: &ImageBarrier) -> vk::ImageMemoryBarrier {
+    vk::ImageMemoryBarrier {
+        src_access_mask: src_access_mask(barrier.prev_accesses),
+        dst_access_mask: dst_access_mask(barrier.next_accesses),
+        old_layout: match barrier.discard {
+            true => vk::ImageLayout::UNDEFINED,
+            false => image_layout(barrier.prev_accesses),
+        },
+        new_layout: image_layout(barrier.next_accesses),
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image: barrier.image,
+        subresource_range: barrier.range,
+        ..Default::default()
+    }
+}
+
+fn src_access_mask2(prev_accesses: &[AccessType]) -> vk::AccessFlags2 {
+    prev_accesses
+        .iter()
+        .filter(|access| is_write_access(**access))
+        .fold(vk::AccessFlags2::empty(), |mask, access| {
+            mask | get_access_info2(*access).access_mask
+        })
+}
+
+fn dst_access_mask2(next_accesses: &[AccessType]) -> vk::AccessFlags2 {
+    next_accesses
+        .iter()
+        .fold(vk::AccessFlags2::empty(), |mask, access| {
+            mask | get_access_info2(*access).access_mask
+        })
+}
+
+pub fn stage_mask2(accesses: &[AccessType]) -> vk::PipelineStageFlags2 {
+    accesses
+        .iter()
+        .fold(vk::PipelineStageFlags2::NONE, |mask, access| {
+            mask | get_access_info2(*access).stage_mask
+        })
+}
+
+pub fn get_memory_barrier2(barrier: &GlobalBarrier) -> vk::MemoryBarrier2 {
+    vk::MemoryBarrier2 {
+        src_stage_mask: stage_mask2(barrier.prev_accesses),
+        src_access_mask: src_access_mask2(barrier.prev_accesses),
+        dst_stage_mask: stage_mask2(barrier.next_accesses),
+        dst_access_mask: dst_access_mask2(barrier.next_accesses),
+        ..Default::default()
+    }
+}
+
+pub fn get_buffer_memory_barrier2(barrier: &BufferBarrier) -> vk::BufferMemoryBarrier2 {
+    vk::BufferMemoryBarrier2 {
+        src_stage_mask: stage_mask2(barrier.prev_accesses),
+        src_access_mask: src_access_mask2(barrier.prev_accesses),
+        dst_stage_mask: stage_mask2(barrier.next_accesses),
+        dst_access_mask: dst_access_mask2(barrier.next_accesses),
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        buffer: barrier.buffer,
+        offset: barrier.offset,
+        size: barrier.size,
+        ..Default::default()
+    }
+}
+
+pub fn get_image_memory_barrier2(barrier: &ImageBarrier) -> vk::ImageMemoryBarrier2 {
+    vk::ImageMemoryBarrier2 {
+        src_stage_mask: stage_mask2(barrier.prev_accesses),
+        src_access_mask: src_access_mask2(barrier.prev_accesses),
+        dst_stage_mask: stage_mask2(barrier.next_accesses),
+        dst_access_mask: dst_access_mask2(barrier.next_accesses),
+        old_layout: match barrier.discard {
+            true => vk::ImageLayout::UNDEFINED,
+            false => image_layout(barrier.prev_accesses),
+        },
+        new_layout: image_layout(barrier.next_accesses),
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image: barrier.image,
+        subresource_range: barrier.range,
+        ..Default::default()
+    }
+}
+
+// Records all barriers with one pipeline barrier, using synchronization2 if the device enabled it.
+pub fn record_barriers(
+    device: &SharedDevice,
+    cb: vk::CommandBuffer,
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) {
+    if global_barrier.is_none() && buffer_barriers.is_empty() && image_barriers.is_empty() {
+        return;
+    }
+
+    if let Some(hazard_checker) = &device.hazard_checker {
+        hazard_checker.lock().unwrap().record_barriers(cb, global_barrier, buffer_barriers, image_barriers);
+    }
+
+    match &device.synchronization2 {
+        Some(synchronization2) => record_barriers2(
+            synchronization2,
+            cb,
+            global_barrier,
+            buffer_barriers,
+            image_barriers,
+        ),
+        None => record_barriers_legacy(device, cb, global_barrier, buffer_barriers, image_barriers),
+    }
+}
+
+pub struct LegacyBarriers {
+    pub src_stage_mask: vk::PipelineStageFlags,
+    pub dst_stage_mask: vk::PipelineStageFlags,
+    pub memory_barriers: Vec<vk::MemoryBarrier>,
+    pub buffer_memory_barriers: Vec<vk::BufferMemoryBarrier>,
+    pub image_memory_barriers: Vec<vk::ImageMemoryBarrier>,
+}
+
+pub fn get_legacy_barriers(
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) -> LegacyBarriers {
+
+    let mut src_stage_mask = vk::PipelineStageFlags::empty();
+    let mut dst_stage_mask = vk::PipelineStageFlags::empty();
+
+    let memory_barriers = global_barrier
+        .map(|barrier| {
+            src_stage_mask |= stage_mask(barrier.prev_accesses);
+            dst_stage_mask |= stage_mask(barrier.next_accesses);
+            get_memory_barrier(barrier)
+        })
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let buffer_memory_barriers = buffer_barriers
+        .iter()
+        .map(|barrier| {
+            src_stage_mask |= stage_mask(barrier.prev_accesses);
+            dst_stage_mask |= stage_mask(barrier.next_accesses);
+            get_buffer_memory_barrier(barrier)
+        })
+        .collect::<Vec<_>>();
+
+    let image_memory_barriers = image_barriers
+        .iter()
+        .map(|barrier| {
+            src_stage_mask |= stage_mask(barrier.prev_accesses);
+            dst_stage_mask |= stage_mask(barrier.next_accesses);
+            get_image_memory_barrier(barrier)
+        })
+        .collect::<Vec<_>>();
+
+    if src_stage_mask.is_empty() {
+        src_stage_mask = vk::PipelineStageFlags::TOP_OF_PIPE;
+    }
+    if dst_stage_mask.is_empty() {
+        dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+    }
+
+    LegacyBarriers {
+        src_stage_mask,
+        dst_stage_mask,
+        memory_barriers,
+        buffer_memory_barriers,
+        image_memory_barriers,
+    }
+}
+
+pub fn record_barriers_legacy(
+    device: &SharedDevice,
+    cb: vk::CommandBuffer,
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) {
+    let barriers = get_legacy_barriers(global_barrier, buffer_barriers, image_barriers);
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            cb,
+            barriers.src_stage_mask,
+            barriers.dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &barriers.memory_barriers,
+            &barriers.buffer_memory_barriers,
+            &barriers.image_memory_barriers,
+        );
+    }
+}
+
+pub fn get_dependency_info<'a>(
+    memory_barriers: &'a [vk::MemoryBarrier2],
+    buffer_memory_barriers: &'a [vk::BufferMemoryBarrier2],
+    image_memory_barriers: &'a [vk::ImageMemoryBarrier2],
+) -> vk::DependencyInfoBuilder<'a> {
+    vk::DependencyInfo::builder()
+        .memory_barriers(memory_barriers)
+        .buffer_memory_barriers(buffer_memory_barriers)
+        .image_memory_barriers(image_memory_barriers)
+}
+
+pub struct Barriers2 {
+    pub memory_barriers: Vec<vk::MemoryBarrier2>,
+    pub buffer_memory_barriers: Vec<vk::BufferMemoryBarrier2>,
+    pub image_memory_barriers: Vec<vk::ImageMemoryBarrier2>,
+}
+
+impl Barriers2 {
+    pub fn dependency_info(&self) -> vk::DependencyInfoBuilder<'_> {
+        get_dependency_info(&self.memory_barriers, &self.buffer_memory_barriers, &self.image_memory_barriers)
+    }
+}
+
+pub fn get_barriers2(
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) -> Barriers2 {
+    Barriers2 {
+        memory_barriers: global_barrier
+            .map(get_memory_barrier2)
+            .into_iter()
+            .collect(),
+        buffer_memory_barriers: buffer_barriers
+            .iter()
+            .map(get_buffer_memory_barrier2)
+            .collect(),
+        image_memory_barriers: image_barriers
+            .iter()
+            .map(get_image_memory_barrier2)
+            .collect(),
+    }
+}
+
+pub fn record_barriers2(
+    synchronization2: &khr::Synchronization2,
+    cb: vk::CommandBuffer,
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) {
+    let barriers = get_barriers2(global_barrier, buffer_barriers, image_barriers);
+
+    unsafe {
+        synchronization2.cmd_pipeline_barrier2(cb, &barriers.dependency_info());
+    }
+}
+
+pub fn record_image_barrier(device: &SharedDevice, cb: vk::CommandBuffer, barrier: ImageBarrier) {
+    record_barriers(device, cb, None, &[], &[barrier]);
+}
+
+pub fn record_buffer_barrier(device: &SharedDevice, cb: vk::CommandBuffer, barrier: BufferBarrier) {
+    record_barriers(device, cb, None, &[barrier], &[]);
+}
+
+pub fn record_global_barrier(device: &SharedDevice, cb: vk::CommandBuffer, barrier: GlobalBarrier) {
+    record_barriers(device, cb, Some(&barrier), &[], &[]);
+}
+
 // From vk_sync
 pub struct AccessInfo {
     pub stage_mask: vk::PipelineStageFlags,
@@ -342,6 +686,300 @@ pub fn get_access_info(access_type: AccessType) -> AccessInfo {
     }
 }
 
+// Finer grained counterpart of get_access_info for synchronization2.
+pub struct AccessInfo2 {
+    pub stage_mask: vk::PipelineStageFlags2,
+    pub access_mask: vk::AccessFlags2,
+    pub image_layout: vk::ImageLayout,
+}
+
+pub fn get_access_info2(access_type: AccessType) -> AccessInfo2 {
+    match access_type {
+        AccessType::Nothing => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::NONE,
+            access_mask: vk::AccessFlags2::NONE,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::CommandBufferReadNVX => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COMMAND_PREPROCESS_NV,
+            access_mask: vk::AccessFlags2::COMMAND_PREPROCESS_READ_NV,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::IndirectBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::DRAW_INDIRECT,
+            access_mask: vk::AccessFlags2::INDIRECT_COMMAND_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::IndexBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::INDEX_INPUT,
+            access_mask: vk::AccessFlags2::INDEX_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::VertexBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
+            access_mask: vk::AccessFlags2::VERTEX_ATTRIBUTE_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::VertexShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::VertexShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::VertexShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::TessellationControlShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_CONTROL_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::TessellationControlShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_CONTROL_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::TessellationControlShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_CONTROL_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::TessellationEvaluationShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_EVALUATION_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::TessellationEvaluationShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_EVALUATION_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::TessellationEvaluationShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_EVALUATION_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::GeometryShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::GEOMETRY_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::GeometryShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::GEOMETRY_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::GeometryShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::GEOMETRY_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::FragmentShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::FragmentShaderReadColorInputAttachment => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags2::INPUT_ATTACHMENT_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::FragmentShaderReadDepthStencilInputAttachment => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags2::INPUT_ATTACHMENT_READ,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        },
+        AccessType::FragmentShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::ColorAttachmentRead => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ,
+            image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        },
+        AccessType::DepthStencilAttachmentRead => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        },
+        AccessType::ComputeShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::ComputeShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::ComputeShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::AnyShaderReadUniformBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS
+                | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                | vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::AnyShaderReadUniformBufferOrVertexBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT
+                | vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS
+                | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                | vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::UNIFORM_READ
+                | vk::AccessFlags2::VERTEX_ATTRIBUTE_READ,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS
+                | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                | vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_SAMPLED_READ,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        },
+        AccessType::AnyShaderReadOther => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS
+                | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                | vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::TransferRead => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::ALL_TRANSFER,
+            access_mask: vk::AccessFlags2::TRANSFER_READ,
+            image_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        },
+        AccessType::HostRead => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::HOST,
+            access_mask: vk::AccessFlags2::HOST_READ,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::Present => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::NONE,
+            access_mask: vk::AccessFlags2::NONE,
+            image_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        },
+        AccessType::CommandBufferWriteNVX => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COMMAND_PREPROCESS_NV,
+            access_mask: vk::AccessFlags2::COMMAND_PREPROCESS_WRITE_NV,
+            image_layout: vk::ImageLayout::UNDEFINED,
+        },
+        AccessType::VertexShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::TessellationControlShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_CONTROL_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::TessellationEvaluationShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::TESSELLATION_EVALUATION_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::GeometryShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::GEOMETRY_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::FragmentShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::ColorAttachmentWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        },
+        AccessType::DepthStencilAttachmentWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        },
+        AccessType::DepthAttachmentWriteStencilReadOnly => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+            image_layout: vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL,
+        },
+        AccessType::StencilAttachmentWriteDepthReadOnly => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+            image_layout: vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL,
+        },
+        AccessType::ComputeShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::AnyShaderWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS
+                | vk::PipelineStageFlags2::FRAGMENT_SHADER
+                | vk::PipelineStageFlags2::COMPUTE_SHADER,
+            access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::TransferWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::ALL_TRANSFER,
+            access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            image_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        },
+        AccessType::HostWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::HOST,
+            access_mask: vk::AccessFlags2::HOST_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+        AccessType::ColorAttachmentReadWrite => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ
+                | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            image_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        },
+        AccessType::General => AccessInfo2 {
+            stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            access_mask: vk::AccessFlags2::MEMORY_READ
+                | vk::AccessFlags2::MEMORY_WRITE,
+            image_layout: vk::ImageLayout::GENERAL,
+        },
+    }
+}
+
+pub fn is_write_access(access_type: AccessType) -> bool {
+    get_access_info(access_type).access_mask.intersects(
+        vk::AccessFlags::SHADER_WRITE
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            | vk::AccessFlags::TRANSFER_WRITE
+            | vk::AccessFlags::HOST_WRITE
+            | vk::AccessFlags::MEMORY_WRITE
+            | vk::AccessFlags::COMMAND_PREPROCESS_WRITE_NV,
+    )
+}
+
 pub fn image_aspect_mask_from_format(format: vk::Format) -> vk::ImageAspectFlags {
     match format {
         vk::Format::D16_UNORM => vk::ImageAspectFlags::DEPTH,
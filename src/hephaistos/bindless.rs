@@ -0,0 +1,221 @@
+use super::*;
+use ash::vk;
+use std::sync::atomic::Ordering;
+
+// Bindings of the bindless set, e.g. in GLSL with GL_EXT_nonuniform_qualifier:
+// layout(set = N, binding = 0) uniform texture2D textures[];
+// layout(set = N, binding = 1) buffer Buffers{ ... } buffers[];
+// layout(set = N, binding = 2) uniform sampler samplers[];
+pub const BINDLESS_SAMPLED_IMAGE_BINDING: u32 = 0;
+pub const BINDLESS_STORAGE_BUFFER_BINDING: u32 = 1;
+pub const BINDLESS_SAMPLER_BINDING: u32 = 2;
+
+impl BindlessSlots{
+    fn new(capacity: u32) -> Self{
+        Self{
+            capacity,
+            next_unused: 0,
+            free: Vec::new(),
+            released: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, frame_index: u64, kind: &str) -> u32{
+        let (reusable, released) = self.released
+            .drain(..)
+            .partition::<Vec<_>, _>(|(released_frame, _)| released_frame + FRAMES_IN_FLIGHT as u64 <= frame_index);
+        self.released = released;
+        self.free.extend(reusable.into_iter().map(|(_, index)| index));
+
+        match self.free.pop(){
+            Some(index) => index,
+            None => {
+                assert!(self.next_unused < self.capacity, "BindlessHeap is out of {} slots ({})", kind, self.capacity);
+                self.next_unused += 1;
+                self.next_unused - 1
+            }
+        }
+    }
+
+    fn release(&mut self, frame_index: u64, index: u32, kind: &str){
+        assert!(
+            index < self.next_unused && !self.free.contains(&index) && !self.released.iter().any(|(_, i)| *i == index),
+            "{} index {} is not registered with the BindlessHeap", kind, index
+        );
+        self.released.push((frame_index, index));
+    }
+}
+
+impl RenderDevice{
+    pub fn create_bindless_heap(&self, desc: &BindlessHeapDesc) -> BindlessHeap{
+        assert!(self.descriptor_indexing, "BindlessHeap needs DeviceDesc::descriptor_indexing");
+
+        let mut indexing_properties = vk::PhysicalDeviceDescriptorIndexingProperties::default();
+        unsafe{
+            self.instance.raw.get_physical_device_properties2(
+                self.adapter.pdevice,
+                &mut vk::PhysicalDeviceProperties2::builder().push_next(&mut indexing_properties),
+            );
+        }
+        assert!(
+            desc.sampled_images <= indexing_properties.max_per_stage_descriptor_update_after_bind_sampled_images
+                && desc.storage_buffers <= indexing_properties.max_per_stage_descriptor_update_after_bind_storage_buffers
+                && desc.samplers <= indexing_properties.max_per_stage_descriptor_update_after_bind_samplers,
+            "BindlessHeapDesc {:?} exceeds the adapter's update after bind descriptor limits",
+            desc
+        );
+
+        // Slots can be written while the set is bound, as long as the shaders do not access them.
+        let flags = vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING
+            | vk::DescriptorBindingFlags::PARTIALLY_BOUND;
+        let bindings = [
+            (BINDLESS_SAMPLED_IMAGE_BINDING, vk::DescriptorType::SAMPLED_IMAGE, desc.sampled_images),
+            (BINDLESS_STORAGE_BUFFER_BINDING, vk::DescriptorType::STORAGE_BUFFER, desc.storage_buffers),
+            (BINDLESS_SAMPLER_BINDING, vk::DescriptorType::SAMPLER, desc.samplers),
+        ].map(|(binding, descriptor_type, count)| DescriptorBindingDesc{
+            binding,
+            descriptor_type,
+            count,
+            stage_flags: vk::ShaderStageFlags::ALL,
+            flags,
+        });
+        let layout = self.get_descriptor_set_layout(&DescriptorSetLayoutDesc{bindings: &bindings});
+
+        let pool_sizes = bindings
+            .iter()
+            .map(|binding| vk::DescriptorPoolSize{
+                ty: binding.descriptor_type,
+                descriptor_count: binding.count,
+            })
+            .collect::<Vec<_>>();
+        let (pool, set) = unsafe{
+            let pool = self.raw
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::builder()
+                        .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+                        .max_sets(1)
+                        .pool_sizes(&pool_sizes),
+                    None,
+                )
+                .expect("Could not create bindless descriptor pool");
+            let set = self.raw
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(pool)
+                        .set_layouts(&[layout.raw]),
+                )
+                .expect("Could not allocate bindless descriptor set")[0];
+            (pool, set)
+        };
+
+        BindlessHeap{
+            set,
+            pool,
+            layout,
+            state: Mutex::new(BindlessHeapState{
+                sampled_images: BindlessSlots::new(desc.sampled_images),
+                storage_buffers: BindlessSlots::new(desc.storage_buffers),
+                samplers: BindlessSlots::new(desc.samplers),
+                owned_samplers: FxHashMap::default(),
+                retired_samplers: Vec::new(),
+            }),
+            device: self.shared.clone(),
+        }
+    }
+}
+
+impl BindlessHeap{
+    // The returned index stays valid until it is released, the view has to stay alive until then.
+    pub fn register_image(&self, view: &ImageView) -> u32{
+        let mut state = self.state.lock().unwrap();
+        let index = state.sampled_images.allocate(self.device.frame_index.load(Ordering::Relaxed), "sampled image");
+        DescriptorSetWriter::new()
+            .sampled_image_at(BINDLESS_SAMPLED_IMAGE_BINDING, index, view)
+            .write(&self.device, self.set);
+        index
+    }
+    pub fn register_storage_buffer(&self, buffer: &Buffer) -> u32{
+        let mut state = self.state.lock().unwrap();
+        let index = state.storage_buffers.allocate(self.device.frame_index.load(Ordering::Relaxed), "storage buffer");
+        DescriptorSetWriter::new()
+            .storage_buffer_at(BINDLESS_STORAGE_BUFFER_BINDING, index, buffer)
+            .write(&self.device, self.set);
+        index
+    }
+    // The heap creates and owns the sampler, it is destroyed once the index is released.
+    pub fn register_sampler(&self, desc: SamplerDesc) -> u32{
+        let mut state = self.state.lock().unwrap();
+        let frame_index = self.device.frame_index.load(Ordering::Relaxed);
+        self.destroy_retired_samplers(&mut state, frame_index);
+        let index = state.samplers.allocate(frame_index, "sampler");
+        let sampler = self.device.create_uncached_sampler(desc);
+        DescriptorSetWriter::new()
+            .sampler_at(BINDLESS_SAMPLER_BINDING, index, sampler)
+            .write(&self.device, self.set);
+        state.owned_samplers.insert(index, sampler);
+        index
+    }
+
+    fn destroy_retired_samplers(&self, state: &mut BindlessHeapState, frame_index: u64){
+        state.retired_samplers.retain(|(retired_frame, sampler)| {
+            let in_use = retired_frame + FRAMES_IN_FLIGHT as u64 > frame_index;
+            if !in_use{
+                unsafe{
+                    self.device.destroy_sampler(sampler.raw, None);
+                }
+            }
+            in_use
+        });
+    }
+
+    // Released indices are only handed out again once the frames that could still use them have finished.
+    pub fn release_image(&self, index: u32){
+        let frame_index = self.device.frame_index.load(Ordering::Relaxed);
+        self.state.lock().unwrap().sampled_images.release(frame_index, index, "Sampled image");
+    }
+    pub fn release_storage_buffer(&self, index: u32){
+        let frame_index = self.device.frame_index.load(Ordering::Relaxed);
+        self.state.lock().unwrap().storage_buffers.release(frame_index, index, "Storage buffer");
+    }
+    pub fn release_sampler(&self, index: u32){
+        let frame_index = self.device.frame_index.load(Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+        state.samplers.release(frame_index, index, "Sampler");
+        let sampler = state.owned_samplers.remove(&index).unwrap();
+        state.retired_samplers.push((frame_index, sampler));
+        self.destroy_retired_samplers(&mut state, frame_index);
+    }
+
+    // The pipeline has to use the heap's layout at set_index, see GraphicsPipelineDesc::set_layout_overrides.
+    pub fn bind(&self, cb: vk::CommandBuffer, pipeline: &Pipeline, set_index: u32){
+        assert!(
+            pipeline.set_layouts.get(set_index as usize) == Some(&self.layout.raw),
+            "Pipeline layout at set {} is not the BindlessHeap layout",
+            set_index
+        );
+        unsafe{
+            self.device.cmd_bind_descriptor_sets(
+                cb,
+                pipeline.bind_point,
+                pipeline.layout,
+                set_index,
+                &[self.set],
+                &[],
+            );
+        }
+    }
+}
+
+impl Drop for BindlessHeap{
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap();
+        unsafe{
+            self.device.destroy_descriptor_pool(self.pool, None);
+            let samplers = state.owned_samplers.values().chain(state.retired_samplers.iter().map(|(_, sampler)| sampler));
+            for sampler in samplers{
+                self.device.destroy_sampler(sampler.raw, None);
+            }
+        }
+    }
+}
@@ -1,10 +1,6 @@
 use super::*;
-use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
+use ash::vk;
 use gpu_allocator::vulkan::{AllocationCreateDesc, Allocator};
-use gpu_allocator::MemoryLocation;
-use std::borrow::BorrowMut;
-use std::sync::{Arc, Weak};
 
 pub trait CreateBuffer {
     fn create_buffer_alloc(&self, allocator: &mut Allocator, desc: BufferDesc) -> Buffer;
@@ -13,12 +9,18 @@ pub trait CreateBuffer {
 
 impl CreateBuffer for RenderDevice{
     fn create_buffer_alloc(&self, allocator: &mut Allocator, desc: BufferDesc) -> Buffer {
-        let buffer_info = vk::BufferCreateInfo {
+        let mut buffer_info = vk::BufferCreateInfo {
             size: desc.size as u64,
             usage: desc.usage,
             sharing_mode: vk::SharingMode::EXCLUSIVE,
             ..Default::default()
         };
+        let queue_family_indices = self.concurrent_queue_family_indices();
+        if let Some(queue_family_indices) = &queue_family_indices {
+            buffer_info.sharing_mode = vk::SharingMode::CONCURRENT;
+            buffer_info.queue_family_index_count = queue_family_indices.len() as u32;
+            buffer_info.p_queue_family_indices = queue_family_indices.as_ptr();
+        }
 
         let buffer = unsafe {
             self.raw
@@ -49,6 +51,10 @@ impl CreateBuffer for RenderDevice{
                 .expect("Could not bind buffer memory.");
         }
 
+        if let (Some(hazard_checker), Some(label)) = (&self.hazard_checker, desc.label) {
+            hazard_checker.lock().unwrap().set_name(ResourceId::Buffer(buffer), label);
+        }
+
         Buffer {
             raw: buffer,
             desc: (desc).into(),
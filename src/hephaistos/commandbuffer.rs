@@ -2,13 +2,24 @@ use super::*;
 
 pub trait CreateCommandBuffer {
     fn create_command_buffer(&self) -> CommandBuffer;
+    // For the dedicated compute queue if there is one, the global queue otherwise.
+    fn create_compute_command_buffer(&self) -> CommandBuffer;
+    fn create_command_buffer_for_queue_family(&self, queue_family_index: u32) -> CommandBuffer;
 }
 
 impl CreateCommandBuffer for Arc<SharedDevice> {
     fn create_command_buffer(&self) -> CommandBuffer {
+        self.create_command_buffer_for_queue_family(self.queue_family_index)
+    }
+    fn create_compute_command_buffer(&self) -> CommandBuffer {
+        self.create_command_buffer_for_queue_family(
+            self.compute_queue_family_index.unwrap_or(self.queue_family_index),
+        )
+    }
+    fn create_command_buffer_for_queue_family(&self, queue_family_index: u32) -> CommandBuffer {
         let pool_create_info = vk::CommandPoolCreateInfo::builder()
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-            .queue_family_index(self.queue_family_index);
+            .queue_family_index(queue_family_index);
 
         let pool = unsafe {
             self.raw
@@ -1,6 +1,8 @@
 
 use super::*;
 use ash::vk;
+use std::ffi::{CStr, CString};
+use std::path::PathBuf;
 
 pub struct AdapterDesc<'a>{
     pub compatible_surface: Option<&'a Surface>,
@@ -8,7 +10,17 @@ pub struct AdapterDesc<'a>{
     //pub limits: vk::PhysicalDeviceLimits,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Default)]
+pub struct DeviceDesc{
+    // Enables VK_KHR_dynamic_rendering, panics if the adapter does not support it.
+    pub dynamic_rendering: bool,
+    // Enables VK_EXT_descriptor_indexing for BindlessHeap, panics if the adapter does not support it.
+    pub descriptor_indexing: bool,
+    // Pipeline cache loaded at startup and written back when the device is dropped.
+    pub pipeline_cache_path: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ImageType{
     Tex1d,
     Tex1dArray,
@@ -19,7 +31,7 @@ pub enum ImageType{
     CubeArray,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ImageDesc{
     pub image_type: ImageType,
     pub usage: vk::ImageUsageFlags,
@@ -29,6 +41,7 @@ pub struct ImageDesc{
     pub tiling: vk::ImageTiling,
     pub mip_levels: u32,
     pub array_elements: u32,
+    pub sample_count: vk::SampleCountFlags,
 }
 
 impl Default for ImageDesc{
@@ -42,6 +55,41 @@ impl Default for ImageDesc{
             tiling: Default::default(),
             mip_levels: 1,
             array_elements: 1,
+            sample_count: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+}
+
+impl ImageDesc{
+    pub fn array_layers(&self) -> u32{
+        match self.image_type{
+            ImageType::Tex1dArray | ImageType::Tex2dArray => self.array_elements,
+            ImageType::Cube => 6,
+            ImageType::CubeArray => 6 * self.array_elements,
+            _ => 1,
+        }
+    }
+    pub fn subresource_count(&self) -> usize{
+        (self.mip_levels * self.array_layers()) as usize
+    }
+}
+
+// vk::ComponentMapping does not implement Hash, so views store their swizzle in this form.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Swizzle{
+    pub r: vk::ComponentSwizzle,
+    pub g: vk::ComponentSwizzle,
+    pub b: vk::ComponentSwizzle,
+    pub a: vk::ComponentSwizzle,
+}
+
+impl From<Swizzle> for vk::ComponentMapping{
+    fn from(src: Swizzle) -> Self {
+        vk::ComponentMapping{
+            r: src.r,
+            g: src.g,
+            b: src.b,
+            a: src.a,
         }
     }
 }
@@ -52,19 +100,226 @@ pub struct ImageViewDesc{
     pub format: Option<vk::Format>,
     pub aspect_mask: vk::ImageAspectFlags,
     pub base_mip_level: u32,
+    // None selects all remaining mip levels.
     pub level_count: Option<u32>,
+    pub base_array_layer: u32,
+    // None selects one layer for 1D, 2D and 3D views, six for cube views and all remaining
+    // array layers otherwise.
+    pub layer_count: Option<u32>,
+    pub swizzle: Swizzle,
+}
+
+#[derive(Clone, Copy)]
+pub struct DepthStencilAttachmentDesc{
+    pub format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    pub depth_load_op: vk::AttachmentLoadOp,
+    pub depth_store_op: vk::AttachmentStoreOp,
+    // Ignored for formats without a stencil aspect.
+    pub stencil_load_op: vk::AttachmentLoadOp,
+    pub stencil_store_op: vk::AttachmentStoreOp,
+    pub initial_layout: vk::ImageLayout,
+    pub final_layout: vk::ImageLayout,
+    // Layout during the subpasses, a read only layout allows sampling depth while depth testing.
+    pub layout: vk::ImageLayout,
+}
+
+impl Default for DepthStencilAttachmentDesc{
+    fn default() -> Self {
+        Self{
+            format: vk::Format::UNDEFINED,
+            samples: vk::SampleCountFlags::TYPE_1,
+            depth_load_op: vk::AttachmentLoadOp::CLEAR,
+            depth_store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        }
+    }
+}
+
+impl DepthStencilAttachmentDesc{
+    // Keeps existing depth and stencil contents and only tests against them, e.g. for decals
+    // or soft particles that sample the depth buffer in the same pass.
+    pub fn read_only(format: vk::Format, samples: vk::SampleCountFlags) -> Self{
+        Self{
+            format,
+            samples,
+            depth_load_op: vk::AttachmentLoadOp::LOAD,
+            depth_store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::LOAD,
+            stencil_store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        }
+    }
+}
+
+impl From<DepthStencilAttachmentDesc> for vk::AttachmentDescription{
+    fn from(src: DepthStencilAttachmentDesc) -> Self {
+        vk::AttachmentDescription{
+            format: src.format,
+            samples: src.samples,
+            load_op: src.depth_load_op,
+            store_op: src.depth_store_op,
+            stencil_load_op: src.stencil_load_op,
+            stencil_store_op: src.stencil_store_op,
+            initial_layout: src.initial_layout,
+            final_layout: src.final_layout,
+            ..Default::default()
+        }
+    }
 }
 
 pub struct RenderPassDesc<'a>{
     pub color_attachments: &'a [vk::AttachmentDescription],
-    pub depth_attachment: Option<vk::AttachmentDescription>,
+    pub depth_attachment: Option<DepthStencilAttachmentDesc>,
+    // Either empty or one resolve target per color attachment.
+    pub resolve_attachments: &'a [vk::AttachmentDescription],
+    // Empty creates a single subpass using all attachments.
+    pub subpasses: &'a [SubpassDesc<'a>],
+    pub dependencies: &'a [SubpassDependencyDesc<'a>],
+    // Either empty or one per subpass, bit n renders the subpass to array layer n (gl_ViewIndex).
+    pub view_masks: &'a [u32],
+    // Sets of views that may be rendered concurrently, e.g. both eyes of a stereo pair.
+    pub correlation_masks: &'a [u32],
+}
+
+// Attachments are referenced by their index in the render pass, which orders
+// color attachments first, then the depth attachment, then resolve attachments.
+#[derive(Clone, Copy, Default)]
+pub struct SubpassDesc<'a>{
+    pub color_attachments: &'a [u32],
+    pub input_attachments: &'a [u32],
+    // Either empty or one per color attachment, vk::ATTACHMENT_UNUSED skips a color attachment.
+    pub resolve_attachments: &'a [u32],
+    pub depth_attachment: Option<u32>,
+    // Overrides DepthStencilAttachmentDesc::layout for this subpass, e.g. to read depth in a later subpass.
+    pub depth_layout: Option<vk::ImageLayout>,
+    pub preserve_attachments: &'a [u32],
+}
+
+pub struct SubpassDependencyDesc<'a>{
+    // vk::SUBPASS_EXTERNAL refers to commands outside of the render pass.
+    pub src_subpass: u32,
+    pub dst_subpass: u32,
+    pub prev_accesses: &'a [vk_sync::AccessType],
+    pub next_accesses: &'a [vk_sync::AccessType],
+    pub by_region: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearColor{
+    Float([f32; 4]),
+    Int([i32; 4]),
+    Uint([u32; 4]),
+}
+
+impl Default for ClearColor{
+    fn default() -> Self {
+        ClearColor::Float([0.0; 4])
+    }
+}
+
+impl From<ClearColor> for vk::ClearValue{
+    fn from(src: ClearColor) -> Self {
+        vk::ClearValue{
+            color: match src{
+                ClearColor::Float(float32) => vk::ClearColorValue{float32},
+                ClearColor::Int(int32) => vk::ClearColorValue{int32},
+                ClearColor::Uint(uint32) => vk::ClearColorValue{uint32},
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClearDepthStencil{
+    pub depth: f32,
+    pub stencil: u32,
+}
+
+impl Default for ClearDepthStencil{
+    fn default() -> Self {
+        Self{
+            depth: 1.0,
+            stencil: 0,
+        }
+    }
+}
+
+impl From<ClearDepthStencil> for vk::ClearValue{
+    fn from(src: ClearDepthStencil) -> Self {
+        vk::ClearValue{
+            depth_stencil: vk::ClearDepthStencilValue{
+                depth: src.depth,
+                stencil: src.stencil,
+            },
+        }
+    }
 }
 
 pub struct RenderPassBeginnDesc<'a>{
     pub color_attachments: &'a [&'a ImageView],
     pub depth_attachment: Option<&'a ImageView>,
+    pub resolve_attachments: &'a [&'a ImageView],
+    // None renders to the whole extent shared by all attachments.
+    pub area: Option<vk::Rect2D>,
+    // Either empty or one per color attachment, required for attachments using AttachmentLoadOp::CLEAR.
+    pub color_clear_values: &'a [ClearColor],
+    pub depth_clear_value: Option<ClearDepthStencil>,
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderingAttachmentDesc<'a>{
+    pub view: &'a ImageView,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub clear_value: vk::ClearValue,
+    // Multisampled attachments are resolved into this view at the end of rendering.
+    pub resolve_view: Option<&'a ImageView>,
+}
+
+pub struct RenderingDesc<'a>{
     pub area: vk::Rect2D,
-    pub clear_values: &'a [vk::ClearValue],
+    // Ignored if view_mask is not zero.
+    pub layer_count: u32,
+    pub view_mask: u32,
+    pub color_attachments: &'a [RenderingAttachmentDesc<'a>],
+    // Also used as stencil attachment if the format has a stencil aspect.
+    pub depth_attachment: Option<RenderingAttachmentDesc<'a>>,
+}
+
+// Attachment formats pipelines are created against when using dynamic rendering.
+#[derive(Clone, Copy)]
+pub struct RenderingFormatsDesc<'a>{
+    pub color_formats: &'a [vk::Format],
+    pub depth_format: Option<vk::Format>,
+    pub view_mask: u32,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl<'a> Default for RenderingFormatsDesc<'a>{
+    fn default() -> Self {
+        Self{
+            color_formats: &[],
+            depth_format: None,
+            view_mask: 0,
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+}
+
+// What a graphics pipeline renders into.
+#[derive(Clone, Copy)]
+pub enum PipelineTarget<'a>{
+    // Uses GraphicsPipelineDesc::subpass of the render pass.
+    RenderPass(&'a RenderPass),
+    // Needs DeviceDesc::dynamic_rendering.
+    Rendering(&'a RenderingFormatsDesc<'a>),
 }
 
 #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
@@ -85,7 +340,7 @@ pub struct BufferDesc<'a> {
 impl<'a> From<BufferDesc<'a>> for BufferDescInt {
     fn from(src: BufferDesc<'a>) -> Self {
         BufferDescInt {
-            label: src.label.map(|s| String::from(s)),
+            label: src.label.map(String::from),
             size: src.size,
             usage: src.usage,
             memory_location: src.memory_location,
@@ -100,3 +355,320 @@ pub struct BufferDescInt {
     pub usage: vk::BufferUsageFlags,
     pub memory_location: gpu_allocator::MemoryLocation,
 }
+
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerDesc{
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    pub mip_lod_bias: f32,
+    // None disables anisotropic filtering.
+    pub max_anisotropy: Option<f32>,
+    // None disables depth comparison.
+    pub compare_op: Option<vk::CompareOp>,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub border_color: vk::BorderColor,
+    pub unnormalized_coordinates: bool,
+}
+
+impl Default for SamplerDesc{
+    fn default() -> Self {
+        Self{
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::REPEAT,
+            address_mode_v: vk::SamplerAddressMode::REPEAT,
+            address_mode_w: vk::SamplerAddressMode::REPEAT,
+            mip_lod_bias: 0.0,
+            max_anisotropy: None,
+            compare_op: None,
+            min_lod: 0.0,
+            max_lod: vk::LOD_CLAMP_NONE,
+            border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+            unnormalized_coordinates: false,
+        }
+    }
+}
+
+impl SamplerDesc{
+    // Floats are compared by their bit pattern so the desc can be used as a cache key.
+    fn key(&self) -> impl PartialEq + std::hash::Hash{
+        (
+            self.mag_filter,
+            self.min_filter,
+            self.mipmap_mode,
+            [self.address_mode_u, self.address_mode_v, self.address_mode_w],
+            self.mip_lod_bias.to_bits(),
+            self.max_anisotropy.map(f32::to_bits),
+            self.compare_op,
+            [self.min_lod.to_bits(), self.max_lod.to_bits()],
+            self.border_color,
+            self.unnormalized_coordinates,
+        )
+    }
+}
+
+impl PartialEq for SamplerDesc{
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerDesc{}
+
+impl std::hash::Hash for SamplerDesc{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ShaderStageDesc<'a>{
+    pub stage: vk::ShaderStageFlags,
+    // SPIR-V words.
+    pub code: &'a [u32],
+    pub entry_point: &'a CStr,
+}
+
+impl<'a> ShaderStageDesc<'a>{
+    pub fn new(stage: vk::ShaderStageFlags, code: &'a [u32]) -> Self{
+        Self{
+            stage,
+            code,
+            entry_point: c"main",
+        }
+    }
+    pub fn vertex(code: &'a [u32]) -> Self{
+        Self::new(vk::ShaderStageFlags::VERTEX, code)
+    }
+    pub fn fragment(code: &'a [u32]) -> Self{
+        Self::new(vk::ShaderStageFlags::FRAGMENT, code)
+    }
+    pub fn compute(code: &'a [u32]) -> Self{
+        Self::new(vk::ShaderStageFlags::COMPUTE, code)
+    }
+}
+
+// Shader source file compiled at runtime. GLSL stages are inferred from the
+// .vert/.frag/.comp/.geom/.tesc/.tese extensions, .hlsl files need an explicit stage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderSourceDesc{
+    pub path: PathBuf,
+    pub stage: vk::ShaderStageFlags,
+    pub entry_point: CString,
+}
+
+impl ShaderSourceDesc{
+    pub fn new(path: impl Into<PathBuf>) -> Self{
+        let path = path.into();
+        let stage = shader_stage_from_path(&path)
+            .unwrap_or_else(|| panic!("Cannot infer the shader stage of {:?}, use ShaderSourceDesc::with_stage", path));
+        Self::with_stage(path, stage)
+    }
+    pub fn with_stage(path: impl Into<PathBuf>, stage: vk::ShaderStageFlags) -> Self{
+        let path = path.into();
+        assert!(
+            shader_source_language(&path).is_some(),
+            "{:?} is neither a GLSL nor an HLSL shader, only those can be compiled at runtime",
+            path
+        );
+        Self{
+            path,
+            stage,
+            entry_point: CString::new("main").unwrap(),
+        }
+    }
+    pub fn entry_point(mut self, entry_point: &str) -> Self{
+        self.entry_point = CString::new(entry_point).expect("Entry point contains a nul byte");
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterizationDesc{
+    pub polygon_mode: vk::PolygonMode,
+    pub cull_mode: vk::CullModeFlags,
+    pub front_face: vk::FrontFace,
+    pub line_width: f32,
+}
+
+impl Default for RasterizationDesc{
+    fn default() -> Self {
+        Self{
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct DepthStencilStateDesc{
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare_op: vk::CompareOp,
+    pub stencil_test: bool,
+    pub front: vk::StencilOpState,
+    pub back: vk::StencilOpState,
+}
+
+impl Default for DepthStencilStateDesc{
+    fn default() -> Self {
+        let noop_stencil_state = vk::StencilOpState{
+            fail_op: vk::StencilOp::KEEP,
+            pass_op: vk::StencilOp::KEEP,
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: vk::CompareOp::ALWAYS,
+            ..Default::default()
+        };
+        Self{
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: vk::CompareOp::LESS_OR_EQUAL,
+            stencil_test: false,
+            front: noop_stencil_state,
+            back: noop_stencil_state,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum BlendMode{
+    #[default]
+    Opaque,
+    Alpha,
+    PremultipliedAlpha,
+    Additive,
+    Custom(vk::PipelineColorBlendAttachmentState),
+}
+
+impl From<BlendMode> for vk::PipelineColorBlendAttachmentState{
+    fn from(src: BlendMode) -> Self {
+        let blend = |src_color_blend_factor, dst_color_blend_factor| vk::PipelineColorBlendAttachmentState{
+            blend_enable: vk::TRUE,
+            src_color_blend_factor,
+            dst_color_blend_factor,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        };
+        match src{
+            BlendMode::Opaque => vk::PipelineColorBlendAttachmentState{
+                blend_enable: vk::FALSE,
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+                ..Default::default()
+            },
+            BlendMode::Alpha => blend(vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::PremultipliedAlpha => blend(vk::BlendFactor::ONE, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => vk::PipelineColorBlendAttachmentState{
+                dst_alpha_blend_factor: vk::BlendFactor::ONE,
+                ..blend(vk::BlendFactor::ONE, vk::BlendFactor::ONE)
+            },
+            BlendMode::Custom(state) => state,
+        }
+    }
+}
+
+pub struct GraphicsPipelineDesc<'a>{
+    pub shaders: &'a [ShaderStageDesc<'a>],
+    // Both empty derives a single packed vertex buffer from the vertex shader inputs, otherwise
+    // the attributes are checked against them.
+    pub vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pub topology: vk::PrimitiveTopology,
+    pub rasterization: RasterizationDesc,
+    // Ignored if the subpass has no depth attachment, depth writes are ignored if it is read only.
+    pub depth_stencil: DepthStencilStateDesc,
+    // Either empty for opaque color attachments or one per color attachment of the subpass.
+    pub blend: &'a [BlendMode],
+    pub dynamic_states: &'a [vk::DynamicState],
+    // Empty derives the layouts and push constant ranges from the shaders.
+    pub set_layouts: &'a [vk::DescriptorSetLayout],
+    // Replace the reflected layout at a set index, e.g. with BindlessHeap::layout. Only used
+    // when set_layouts is empty.
+    pub set_layout_overrides: &'a [(u32, &'a DescriptorSetLayout)],
+    pub push_constant_ranges: &'a [vk::PushConstantRange],
+    pub subpass: u32,
+}
+
+impl<'a> Default for GraphicsPipelineDesc<'a>{
+    fn default() -> Self {
+        Self{
+            shaders: &[],
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            rasterization: Default::default(),
+            depth_stencil: Default::default(),
+            blend: &[],
+            dynamic_states: &[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            set_layouts: &[],
+            set_layout_overrides: &[],
+            push_constant_ranges: &[],
+            subpass: 0,
+        }
+    }
+}
+
+pub struct ComputePipelineDesc<'a>{
+    pub shader: ShaderStageDesc<'a>,
+    // Empty derives the layouts and push constant ranges from the shader.
+    pub set_layouts: &'a [vk::DescriptorSetLayout],
+    // See GraphicsPipelineDesc::set_layout_overrides.
+    pub set_layout_overrides: &'a [(u32, &'a DescriptorSetLayout)],
+    pub push_constant_ranges: &'a [vk::PushConstantRange],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DescriptorBindingDesc{
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stage_flags: vk::ShaderStageFlags,
+    // Needs DeviceDesc::descriptor_indexing, UPDATE_AFTER_BIND bindings need an update after bind pool.
+    pub flags: vk::DescriptorBindingFlags,
+}
+
+impl DescriptorBindingDesc{
+    pub fn new(binding: u32, descriptor_type: vk::DescriptorType, stage_flags: vk::ShaderStageFlags) -> Self{
+        Self{
+            binding,
+            descriptor_type,
+            count: 1,
+            stage_flags,
+            flags: vk::DescriptorBindingFlags::empty(),
+        }
+    }
+}
+
+// Identical descriptions share one layout, see SharedDevice::get_descriptor_set_layout.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DescriptorSetLayoutDesc<'a>{
+    pub bindings: &'a [DescriptorBindingDesc],
+}
+
+// Sizes of the arrays in a BindlessHeap, checked against the adapter's update after bind limits.
+#[derive(Clone, Copy, Debug)]
+pub struct BindlessHeapDesc{
+    pub sampled_images: u32,
+    pub storage_buffers: u32,
+    pub samplers: u32,
+}
+
+impl Default for BindlessHeapDesc{
+    fn default() -> Self {
+        Self{
+            sampled_images: 16384,
+            storage_buffers: 4096,
+            samplers: 256,
+        }
+    }
+}
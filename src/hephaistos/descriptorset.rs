@@ -0,0 +1,342 @@
+use super::*;
+use ash::vk;
+
+// Descriptors of each type per set a pool is sized for.
+const DESCRIPTOR_POOL_RATIOS: [(vk::DescriptorType, u32); 11] = [
+    (vk::DescriptorType::SAMPLER, 1),
+    (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4),
+    (vk::DescriptorType::SAMPLED_IMAGE, 4),
+    (vk::DescriptorType::STORAGE_IMAGE, 2),
+    (vk::DescriptorType::UNIFORM_TEXEL_BUFFER, 1),
+    (vk::DescriptorType::STORAGE_TEXEL_BUFFER, 1),
+    (vk::DescriptorType::UNIFORM_BUFFER, 2),
+    (vk::DescriptorType::STORAGE_BUFFER, 2),
+    (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+    (vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, 1),
+    (vk::DescriptorType::INPUT_ATTACHMENT, 1),
+];
+const DESCRIPTOR_POOL_MIN_SETS: u32 = 64;
+const DESCRIPTOR_POOL_MAX_SETS: u32 = 4096;
+
+// Sizes from DESCRIPTOR_POOL_RATIOS, raised so that at least one set with the given bindings fits.
+fn descriptor_pool_sizes(max_sets: u32, bindings: &[DescriptorBindingDesc]) -> Vec<vk::DescriptorPoolSize>{
+    let mut pool_sizes = DESCRIPTOR_POOL_RATIOS
+        .iter()
+        .map(|(ty, ratio)| vk::DescriptorPoolSize{
+            ty: *ty,
+            descriptor_count: ratio * max_sets,
+        })
+        .collect::<Vec<_>>();
+    for binding in bindings{
+        match pool_sizes.iter().position(|pool_size| pool_size.ty == binding.descriptor_type){
+            Some(index) => {
+                let required = bindings
+                    .iter()
+                    .filter(|other| other.descriptor_type == binding.descriptor_type)
+                    .map(|other| other.count)
+                    .sum::<u32>();
+                let pool_size = &mut pool_sizes[index];
+                pool_size.descriptor_count = pool_size.descriptor_count.max(required);
+            }
+            None => pool_sizes.push(vk::DescriptorPoolSize{
+                ty: binding.descriptor_type,
+                descriptor_count: binding.count,
+            }),
+        }
+    }
+    pool_sizes
+}
+
+impl SharedDevice{
+    pub fn get_descriptor_set_layout(&self, desc: &DescriptorSetLayoutDesc) -> Arc<DescriptorSetLayout>{
+        let mut bindings = desc.bindings.to_vec();
+        bindings.sort_by_key(|binding| binding.binding);
+        for pair in bindings.windows(2){
+            assert!(pair[0].binding != pair[1].binding, "Binding {} is used twice in DescriptorSetLayoutDesc", pair[0].binding);
+        }
+
+        let mut layouts = self.descriptor_set_layouts.lock().unwrap();
+        layouts
+            .entry(bindings)
+            .or_insert_with_key(|bindings| {
+                let layout_bindings = bindings
+                    .iter()
+                    .map(|binding| {
+                        vk::DescriptorSetLayoutBinding::builder()
+                            .binding(binding.binding)
+                            .descriptor_type(binding.descriptor_type)
+                            .descriptor_count(binding.count)
+                            .stage_flags(binding.stage_flags)
+                            .build()
+                    })
+                    .collect::<Vec<_>>();
+                let binding_flags = bindings
+                    .iter()
+                    .map(|binding| binding.flags)
+                    .collect::<Vec<_>>();
+                let mut binding_flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+                    .binding_flags(&binding_flags);
+
+                let mut create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+                if binding_flags.iter().any(|flags| !flags.is_empty()){
+                    assert!(self.descriptor_indexing, "Descriptor binding flags need DeviceDesc::descriptor_indexing");
+                    create_info = create_info.push_next(&mut binding_flags_info);
+                }
+                if binding_flags.iter().any(|flags| flags.contains(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND)){
+                    create_info = create_info.flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL);
+                }
+                let raw = unsafe{
+                    self.raw
+                        .create_descriptor_set_layout(&create_info, None)
+                        .expect("Could not create descriptor set layout")
+                };
+                Arc::new(DescriptorSetLayout{
+                    raw,
+                    bindings: bindings.clone(),
+                })
+            })
+            .clone()
+    }
+
+    pub(crate) fn destroy_descriptor_set_layouts(&self){
+        let mut layouts = self.descriptor_set_layouts.lock().unwrap();
+        for (_, layout) in layouts.drain(){
+            unsafe{
+                self.raw.destroy_descriptor_set_layout(layout.raw, None);
+            }
+        }
+    }
+}
+
+impl DescriptorAllocator{
+    pub fn new(device: &Arc<SharedDevice>) -> Self{
+        Self{
+            pools: Vec::new(),
+            current_pool: 0,
+            device: device.clone(),
+        }
+    }
+
+    // Every new pool holds twice as many sets as the previous one, up to DESCRIPTOR_POOL_MAX_SETS,
+    // and always fits at least one set of the layout it is created for.
+    fn create_pool(&self, layout: &DescriptorSetLayout) -> vk::DescriptorPool{
+        let max_sets = DESCRIPTOR_POOL_MIN_SETS
+            .saturating_mul(1 << self.pools.len().min(31))
+            .min(DESCRIPTOR_POOL_MAX_SETS);
+        let pool_sizes = descriptor_pool_sizes(max_sets, &layout.bindings);
+        unsafe{
+            self.device.raw
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::builder()
+                        .max_sets(max_sets)
+                        .pool_sizes(&pool_sizes),
+                    None,
+                )
+                .expect("Could not create descriptor pool")
+        }
+    }
+
+    pub fn allocate(&mut self, layout: &DescriptorSetLayout) -> vk::DescriptorSet{
+        loop{
+            let new_pool = self.current_pool == self.pools.len();
+            if new_pool{
+                let pool = self.create_pool(layout);
+                self.pools.push(pool);
+            }
+
+            let result = unsafe{
+                self.device.raw.allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::builder()
+                        .descriptor_pool(self.pools[self.current_pool])
+                        .set_layouts(&[layout.raw]),
+                )
+            };
+            match result{
+                Ok(sets) => return sets[0],
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY) | Err(vk::Result::ERROR_FRAGMENTED_POOL) => {
+                    assert!(
+                        !new_pool,
+                        "Descriptor set layout with bindings {:?} does not fit into an empty descriptor pool",
+                        layout.bindings
+                    );
+                    self.current_pool += 1;
+                }
+                Err(err) => panic!("Could not allocate descriptor set: {:?}", err),
+            }
+        }
+    }
+
+    // Frees every set allocated so far, none of them may still be in use by the GPU.
+    pub fn reset(&mut self){
+        for pool in self.pools.iter(){
+            unsafe{
+                self.device.raw
+                    .reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())
+                    .expect("Could not reset descriptor pool");
+            }
+        }
+        self.current_pool = 0;
+    }
+}
+
+impl Drop for DescriptorAllocator{
+    fn drop(&mut self) {
+        for pool in self.pools.iter(){
+            unsafe{
+                self.device.raw.destroy_descriptor_pool(*pool, None);
+            }
+        }
+    }
+}
+
+impl DescriptorSetWriter{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    pub fn buffer_range(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: &Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> Self{
+        self.buffer_writes.push((binding, 0, descriptor_type, vk::DescriptorBufferInfo{
+            buffer: buffer.raw,
+            offset,
+            range,
+        }));
+        self
+    }
+    pub fn uniform_buffer(self, binding: u32, buffer: &Buffer) -> Self{
+        self.buffer_range(binding, vk::DescriptorType::UNIFORM_BUFFER, buffer, 0, vk::WHOLE_SIZE)
+    }
+    pub fn storage_buffer_at(mut self, binding: u32, array_element: u32, buffer: &Buffer) -> Self{
+        self.buffer_writes.push((binding, array_element, vk::DescriptorType::STORAGE_BUFFER, vk::DescriptorBufferInfo{
+            buffer: buffer.raw,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }));
+        self
+    }
+    pub fn storage_buffer(self, binding: u32, buffer: &Buffer) -> Self{
+        self.storage_buffer_at(binding, 0, buffer)
+    }
+
+    // Images are expected in the layout the render graph leaves them in for shader reads.
+    pub fn image_sampler_at(mut self, binding: u32, array_element: u32, view: &ImageView, sampler: Sampler) -> Self{
+        self.image_writes.push((binding, array_element, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, vk::DescriptorImageInfo{
+            sampler: sampler.raw,
+            image_view: view.raw,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }));
+        self
+    }
+    pub fn image_sampler(self, binding: u32, view: &ImageView, sampler: Sampler) -> Self{
+        self.image_sampler_at(binding, 0, view, sampler)
+    }
+    pub fn sampled_image_at(mut self, binding: u32, array_element: u32, view: &ImageView) -> Self{
+        self.image_writes.push((binding, array_element, vk::DescriptorType::SAMPLED_IMAGE, vk::DescriptorImageInfo{
+            sampler: vk::Sampler::null(),
+            image_view: view.raw,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }));
+        self
+    }
+    pub fn sampled_image(self, binding: u32, view: &ImageView) -> Self{
+        self.sampled_image_at(binding, 0, view)
+    }
+    pub fn sampler_at(mut self, binding: u32, array_element: u32, sampler: Sampler) -> Self{
+        self.image_writes.push((binding, array_element, vk::DescriptorType::SAMPLER, vk::DescriptorImageInfo{
+            sampler: sampler.raw,
+            image_view: vk::ImageView::null(),
+            image_layout: vk::ImageLayout::UNDEFINED,
+        }));
+        self
+    }
+    pub fn sampler(self, binding: u32, sampler: Sampler) -> Self{
+        self.sampler_at(binding, 0, sampler)
+    }
+    pub fn storage_image(mut self, binding: u32, view: &ImageView) -> Self{
+        self.image_writes.push((binding, 0, vk::DescriptorType::STORAGE_IMAGE, vk::DescriptorImageInfo{
+            sampler: vk::Sampler::null(),
+            image_view: view.raw,
+            image_layout: vk::ImageLayout::GENERAL,
+        }));
+        self
+    }
+
+    pub fn write(&self, device: &ash::Device, set: vk::DescriptorSet){
+        let buffer_writes = self.buffer_writes
+            .iter()
+            .map(|(binding, array_element, descriptor_type, info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(*binding)
+                    .dst_array_element(*array_element)
+                    .descriptor_type(*descriptor_type)
+                    .buffer_info(std::slice::from_ref(info))
+                    .build()
+            });
+        let image_writes = self.image_writes
+            .iter()
+            .map(|(binding, array_element, descriptor_type, info)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(*binding)
+                    .dst_array_element(*array_element)
+                    .descriptor_type(*descriptor_type)
+                    .image_info(std::slice::from_ref(info))
+                    .build()
+            });
+        let writes = buffer_writes.chain(image_writes).collect::<Vec<_>>();
+        unsafe{
+            device.update_descriptor_sets(&writes, &[]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn pool_size(pool_sizes: &[vk::DescriptorPoolSize], ty: vk::DescriptorType) -> u32{
+        pool_sizes
+            .iter()
+            .filter(|pool_size| pool_size.ty == ty)
+            .map(|pool_size| pool_size.descriptor_count)
+            .sum()
+    }
+
+    #[test]
+    fn input_attachment_layout_fits_a_new_pool(){
+        let bindings = [
+            DescriptorBindingDesc::new(0, vk::DescriptorType::INPUT_ATTACHMENT, vk::ShaderStageFlags::FRAGMENT),
+            DescriptorBindingDesc::new(1, vk::DescriptorType::INPUT_ATTACHMENT, vk::ShaderStageFlags::FRAGMENT),
+        ];
+        let pool_sizes = descriptor_pool_sizes(DESCRIPTOR_POOL_MIN_SETS, &bindings);
+        assert!(pool_size(&pool_sizes, vk::DescriptorType::INPUT_ATTACHMENT) >= 2);
+    }
+
+    #[test]
+    fn large_arrays_raise_the_pool_size(){
+        let bindings = [DescriptorBindingDesc{
+            count: 10000,
+            ..DescriptorBindingDesc::new(0, vk::DescriptorType::SAMPLED_IMAGE, vk::ShaderStageFlags::ALL)
+        }];
+        let pool_sizes = descriptor_pool_sizes(DESCRIPTOR_POOL_MIN_SETS, &bindings);
+        assert_eq!(pool_size(&pool_sizes, vk::DescriptorType::SAMPLED_IMAGE), 10000);
+        assert_eq!(pool_size(&pool_sizes, vk::DescriptorType::STORAGE_BUFFER), 2 * DESCRIPTOR_POOL_MIN_SETS);
+    }
+
+    #[test]
+    fn unlisted_descriptor_types_are_added(){
+        let bindings = [DescriptorBindingDesc{
+            count: 3,
+            ..DescriptorBindingDesc::new(0, vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, vk::ShaderStageFlags::ALL)
+        }];
+        let pool_sizes = descriptor_pool_sizes(DESCRIPTOR_POOL_MIN_SETS, &bindings);
+        assert_eq!(pool_size(&pool_sizes, vk::DescriptorType::ACCELERATION_STRUCTURE_KHR), 3);
+    }
+}
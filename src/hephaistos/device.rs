@@ -1,9 +1,6 @@
 use super::*;
 use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
-use gpu_allocator::MemoryLocation;
-use gpu_allocator::vulkan::AllocationCreateDesc;
-use raw_window_handle::HasRawWindowHandle;
+use ash::vk;
 use std::sync::Arc;
 
 impl From<Arc<SharedDevice>> for RenderDevice{
@@ -11,14 +8,11 @@ impl From<Arc<SharedDevice>> for RenderDevice{
         Self{
             shared: src.clone(),
             frames: [
-                Mutex::new(Arc::new(DeviceFrame{
-                    main_cb: src.create_command_buffer(),
-                })),
-                Mutex::new(Arc::new(DeviceFrame{
-                    main_cb: src.create_command_buffer(),
-                })),
+                Mutex::new(Arc::new(DeviceFrame::new(&src))),
+                Mutex::new(Arc::new(DeviceFrame::new(&src))),
             ],
             setup_cb: src.create_command_buffer(),
+            samplers: Mutex::new(FxHashMap::default()),
         }
     }
 }
@@ -30,44 +24,152 @@ impl RenderDevice{
 }
 
 pub trait RequestDevice{
-    fn request_device(&self) -> Arc<RenderDevice>;
+    fn request_device(&self, desc: &DeviceDesc) -> Arc<RenderDevice>;
 }
 
 impl RequestDevice for Arc<Adapter>{
-    fn request_device(&self) -> Arc<RenderDevice>{
+    fn request_device(&self, desc: &DeviceDesc) -> Arc<RenderDevice>{
         unsafe{
-            let device_extension_names_raw = [
+            let mut device_extension_names_raw = vec![
                 khr::Swapchain::name().as_ptr(),
                 vk::KhrImagelessFramebufferFn::name().as_ptr(),
                 vk::KhrBufferDeviceAddressFn::name().as_ptr(),
             ];
 
+            let supported_features = self.instance.raw.get_physical_device_features(self.pdevice);
+
+            let mut supported_synchronization2_feature = vk::PhysicalDeviceSynchronization2Features::default();
+            if self.supports_extension(khr::Synchronization2::name()){
+                self.instance.raw.get_physical_device_features2(
+                    self.pdevice,
+                    &mut vk::PhysicalDeviceFeatures2::builder()
+                        .push_next(&mut supported_synchronization2_feature),
+                );
+            }
+            let synchronization2 = supported_synchronization2_feature.synchronization2 == vk::TRUE;
+
+            let mut supported_multiview_feature = vk::PhysicalDeviceMultiviewFeatures::default();
+            if self.supports_extension(vk::KhrMultiviewFn::name()){
+                self.instance.raw.get_physical_device_features2(
+                    self.pdevice,
+                    &mut vk::PhysicalDeviceFeatures2::builder()
+                        .push_next(&mut supported_multiview_feature),
+                );
+            }
+            let multiview = supported_multiview_feature.multiview == vk::TRUE;
+
+            if desc.dynamic_rendering{
+                let mut supported_dynamic_rendering_feature = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+                if self.supports_extension(khr::DynamicRendering::name()){
+                    self.instance.raw.get_physical_device_features2(
+                        self.pdevice,
+                        &mut vk::PhysicalDeviceFeatures2::builder()
+                            .push_next(&mut supported_dynamic_rendering_feature),
+                    );
+                }
+                assert!(
+                    supported_dynamic_rendering_feature.dynamic_rendering == vk::TRUE,
+                    "Dynamic rendering was requested but is not supported by the adapter"
+                );
+            }
+
+            if desc.descriptor_indexing{
+                let mut supported_descriptor_indexing_feature = vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+                if self.supports_extension(vk::ExtDescriptorIndexingFn::name()){
+                    self.instance.raw.get_physical_device_features2(
+                        self.pdevice,
+                        &mut vk::PhysicalDeviceFeatures2::builder()
+                            .push_next(&mut supported_descriptor_indexing_feature),
+                    );
+                }
+                let supported = &supported_descriptor_indexing_feature;
+                assert!(
+                    supported.runtime_descriptor_array == vk::TRUE
+                        && supported.descriptor_binding_partially_bound == vk::TRUE
+                        && supported.descriptor_binding_update_unused_while_pending == vk::TRUE
+                        && supported.descriptor_binding_sampled_image_update_after_bind == vk::TRUE
+                        && supported.descriptor_binding_storage_buffer_update_after_bind == vk::TRUE
+                        && supported.shader_sampled_image_array_non_uniform_indexing == vk::TRUE
+                        && supported.shader_storage_buffer_array_non_uniform_indexing == vk::TRUE,
+                    "Descriptor indexing was requested but is not supported by the adapter"
+                );
+            }
+
             let mut buffer_device_address_feature = vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+            let mut synchronization2_feature = vk::PhysicalDeviceSynchronization2Features::builder()
+                .synchronization2(true);
+            let mut dynamic_rendering_feature = vk::PhysicalDeviceDynamicRenderingFeatures::builder()
+                .dynamic_rendering(true);
+            let mut multiview_feature = vk::PhysicalDeviceMultiviewFeatures::builder()
+                .multiview(true);
+            let mut descriptor_indexing_feature = vk::PhysicalDeviceDescriptorIndexingFeatures::builder()
+                .runtime_descriptor_array(true)
+                .descriptor_binding_partially_bound(true)
+                .descriptor_binding_update_unused_while_pending(true)
+                .descriptor_binding_sampled_image_update_after_bind(true)
+                .descriptor_binding_storage_buffer_update_after_bind(true)
+                .shader_sampled_image_array_non_uniform_indexing(true)
+                .shader_storage_buffer_array_non_uniform_indexing(true);
             let mut features2 = vk::PhysicalDeviceFeatures2::builder()
-                .push_next(&mut buffer_device_address_feature)
-                .build();
+                .features(vk::PhysicalDeviceFeatures{
+                    sampler_anisotropy: supported_features.sampler_anisotropy,
+                    ..Default::default()
+                })
+                .push_next(&mut buffer_device_address_feature);
+
+            if synchronization2{
+                device_extension_names_raw.push(khr::Synchronization2::name().as_ptr());
+                features2 = features2.push_next(&mut synchronization2_feature);
+            }
+            if multiview{
+                device_extension_names_raw.push(vk::KhrMultiviewFn::name().as_ptr());
+                features2 = features2.push_next(&mut multiview_feature);
+            }
+            if desc.dynamic_rendering{
+                device_extension_names_raw.push(khr::DynamicRendering::name().as_ptr());
+                features2 = features2.push_next(&mut dynamic_rendering_feature);
+            }
+            if desc.descriptor_indexing{
+                device_extension_names_raw.push(vk::ExtDescriptorIndexingFn::name().as_ptr());
+                // Required by VK_EXT_descriptor_indexing.
+                device_extension_names_raw.push(vk::KhrMaintenance3Fn::name().as_ptr());
+                features2 = features2.push_next(&mut descriptor_indexing_feature);
+            }
+            let mut features2 = features2.build();
 
-            let features = vk::PhysicalDeviceFeatures {
-                shader_clip_distance: 1,
-                ..Default::default()
-            };
             let priorities = [1.0];
 
-            let queue_info = vk::DeviceQueueCreateInfo::builder()
-                .queue_family_index(self.queue_family_index)
-                .queue_priorities(&priorities);
+            let queue_infos = std::iter::once(self.queue_family_index)
+                .chain(self.compute_queue_family_index)
+                .map(|queue_family_index| {
+                    vk::DeviceQueueCreateInfo::builder()
+                        .queue_family_index(queue_family_index)
+                        .queue_priorities(&priorities)
+                        .build()
+                })
+                .collect::<Vec<_>>();
 
             let device_create_info = vk::DeviceCreateInfo::builder()
-                .queue_create_infos(std::slice::from_ref(&queue_info))
+                .queue_create_infos(&queue_infos)
                 .enabled_extension_names(&device_extension_names_raw)
-                //.enabled_features(&features)
                 .push_next(&mut features2);
 
             let device: ash::Device = self.instance.raw
                 .create_device(self.pdevice, &device_create_info, None)
                 .unwrap();
 
-            let queue = device.get_device_queue(self.queue_family_index as u32, 0);
+            let queue = device.get_device_queue(self.queue_family_index, 0);
+            let compute_queue = self.compute_queue_family_index
+                .map(|queue_family_index| device.get_device_queue(queue_family_index, 0));
+
+            let synchronization2 = match synchronization2{
+                true => Some(khr::Synchronization2::new(&self.instance.raw, &device)),
+                false => None,
+            };
+            let dynamic_rendering = match desc.dynamic_rendering{
+                true => Some(khr::DynamicRendering::new(&self.instance.raw, &device)),
+                false => None,
+            };
 
             let global_allocator = gpu_allocator::vulkan::Allocator::new(&gpu_allocator::vulkan::AllocatorCreateDesc{
                 instance: self.instance.raw.clone(),
@@ -85,6 +187,7 @@ impl RequestDevice for Arc<Adapter>{
             let global_allocator = Arc::new(Mutex::new(global_allocator));
 
             let memory_properties = self.instance.raw.get_physical_device_memory_properties(self.pdevice);
+            let pipeline_cache = self.create_pipeline_cache(&device, desc.pipeline_cache_path.as_deref());
 
             let shared = Arc::new(SharedDevice{
                 global_allocator,
@@ -93,20 +196,33 @@ impl RequestDevice for Arc<Adapter>{
                 adapter: self.clone(),
                 global_queue: queue,
                 queue_family_index: self.queue_family_index,
+                compute_queue,
+                compute_queue_family_index: self.compute_queue_family_index,
                 memory_properties,
+                features: features2.features,
+                synchronization2,
+                dynamic_rendering,
+                descriptor_indexing: desc.descriptor_indexing,
+                multiview,
+                render_passes: Mutex::new(FxHashMap::default()),
+                frame_index: AtomicU64::new(0),
+                hazard_checker: match cfg!(debug_assertions){
+                    true => Some(Mutex::new(HazardChecker::new())),
+                    false => None,
+                },
+                pipeline_cache,
+                pipeline_cache_path: desc.pipeline_cache_path.clone(),
+                descriptor_set_layouts: Mutex::new(FxHashMap::default()),
             });
 
             Arc::new(RenderDevice{
                 shared: shared.clone(),
                 frames: [
-                    Mutex::new(Arc::new(DeviceFrame{
-                        main_cb: shared.create_command_buffer(),
-                    })),
-                    Mutex::new(Arc::new(DeviceFrame{
-                        main_cb: shared.create_command_buffer(),
-                    })),
+                    Mutex::new(Arc::new(DeviceFrame::new(&shared))),
+                    Mutex::new(Arc::new(DeviceFrame::new(&shared))),
                 ],
                 setup_cb: shared.create_command_buffer(),
+                samplers: Mutex::new(FxHashMap::default()),
             })
         }
     }
@@ -125,6 +241,9 @@ impl SharedDevice {
                 .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
             ).unwrap();
         }
+        if let Some(hazard_checker) = &self.hazard_checker{
+            hazard_checker.lock().unwrap().begin_command_buffer(commandbuffer.raw);
+        }
 
         callback(commandbuffer.raw);
 
@@ -144,6 +263,87 @@ impl SharedDevice {
     pub fn submit(&self, submits: &[vk::SubmitInfo], fence: vk::Fence){
         unsafe{self.raw.queue_submit(self.global_queue, submits, fence).unwrap()}
     }
+    pub fn submit_command_buffer(
+        &self,
+        cb: vk::CommandBuffer,
+        wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags2)],
+        signal_semaphores: &[vk::Semaphore],
+        fence: vk::Fence,
+    ){
+        self.submit_command_buffer_to_queue(self.global_queue, cb, wait_semaphores, signal_semaphores, fence);
+    }
+    // Queue families buffers and images are shared between, None if everything runs on the global queue.
+    pub fn concurrent_queue_family_indices(&self) -> Option<[u32; 2]>{
+        self.compute_queue_family_index
+            .map(|compute_queue_family_index| [self.queue_family_index, compute_queue_family_index])
+    }
+    // The command buffer has to be allocated with CreateCommandBuffer::create_compute_command_buffer.
+    pub fn submit_compute_command_buffer(
+        &self,
+        cb: vk::CommandBuffer,
+        wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags2)],
+        signal_semaphores: &[vk::Semaphore],
+        fence: vk::Fence,
+    ){
+        let queue = self.compute_queue.unwrap_or(self.global_queue);
+        self.submit_command_buffer_to_queue(queue, cb, wait_semaphores, signal_semaphores, fence);
+    }
+    // Uses queue_submit2 when synchronization2 is enabled and falls back to queue_submit otherwise.
+    pub fn submit_command_buffer_to_queue(
+        &self,
+        queue: vk::Queue,
+        cb: vk::CommandBuffer,
+        wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags2)],
+        signal_semaphores: &[vk::Semaphore],
+        fence: vk::Fence,
+    ){
+        match &self.synchronization2{
+            Some(synchronization2) => {
+                let wait_semaphore_infos = wait_semaphores.iter().map(|(semaphore, stage_mask)|{
+                    vk::SemaphoreSubmitInfo::builder()
+                        .semaphore(*semaphore)
+                        .stage_mask(*stage_mask)
+                        .build()
+                }).collect::<Vec<_>>();
+                let signal_semaphore_infos = signal_semaphores.iter().map(|semaphore|{
+                    vk::SemaphoreSubmitInfo::builder()
+                        .semaphore(*semaphore)
+                        .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                        .build()
+                }).collect::<Vec<_>>();
+                let command_buffer_infos = [vk::CommandBufferSubmitInfo::builder()
+                    .command_buffer(cb)
+                    .build()];
+
+                let submit_info = vk::SubmitInfo2::builder()
+                    .wait_semaphore_infos(&wait_semaphore_infos)
+                    .command_buffer_infos(&command_buffer_infos)
+                    .signal_semaphore_infos(&signal_semaphore_infos)
+                    .build();
+
+                unsafe{
+                    synchronization2.queue_submit2(queue, &[submit_info], fence).unwrap();
+                }
+            },
+            None => {
+                let (wait_semaphores, wait_stage_masks): (Vec<_>, Vec<_>) = wait_semaphores.iter()
+                    .map(|(semaphore, stage_mask)| (*semaphore, legacy_stage_mask(*stage_mask)))
+                    .unzip();
+                let command_buffers = [cb];
+
+                let submit_info = vk::SubmitInfo::builder()
+                    .wait_semaphores(&wait_semaphores)
+                    .wait_dst_stage_mask(&wait_stage_masks)
+                    .command_buffers(&command_buffers)
+                    .signal_semaphores(signal_semaphores)
+                    .build();
+
+                unsafe{
+                    self.raw.queue_submit(queue, &[submit_info], fence).unwrap();
+                }
+            },
+        }
+    }
 }
 
 pub fn convert_image_type_to_view_type(image_type: ImageType) -> vk::ImageViewType {
@@ -158,11 +358,34 @@ pub fn convert_image_type_to_view_type(image_type: ImageType) -> vk::ImageViewTy
     }
 }
 
+impl Drop for RenderDevice{
+    fn drop(&mut self) {
+        unsafe{
+            for (_, sampler) in self.samplers.lock().unwrap().iter(){
+                self.shared.destroy_sampler(sampler.raw, None);
+            }
+        }
+    }
+}
+
 impl Drop for SharedDevice {
     fn drop(&mut self) {
+        self.save_pipeline_cache();
+        self.destroy_descriptor_set_layouts();
         unsafe {
+            self.raw.destroy_pipeline_cache(self.pipeline_cache, None);
             self.raw.destroy_device(None);
-            println!("device")
+            log::trace!("device");
         }
     }
 }
+
+// Converts a synchronization2 stage mask for the legacy submit path.
+pub fn legacy_stage_mask(stage_mask: vk::PipelineStageFlags2) -> vk::PipelineStageFlags {
+    match stage_mask.as_raw() {
+        0 => vk::PipelineStageFlags::TOP_OF_PIPE,
+        // Stages above bit 31 have no legacy equivalent.
+        raw if raw > u32::MAX as u64 => vk::PipelineStageFlags::ALL_COMMANDS,
+        raw => vk::PipelineStageFlags::from_raw(raw as u32),
+    }
+}
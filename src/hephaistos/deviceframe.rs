@@ -1,5 +1,16 @@
 
 use super::*;
+use std::sync::atomic::Ordering;
+
+impl DeviceFrame{
+    pub fn new(device: &Arc<SharedDevice>) -> Self{
+        Self{
+            main_cb: device.create_command_buffer(),
+            transient_images: Mutex::new(Vec::new()),
+            descriptor_allocator: Mutex::new(DescriptorAllocator::new(device)),
+        }
+    }
+}
 
 pub trait BeginFrame{
     fn begin_frame(&self) -> Arc<DeviceFrame>;
@@ -16,12 +27,14 @@ impl BeginFrame for RenderDevice{
             self.raw.wait_for_fences(
                 &[frame0.main_cb.submit_done_fence],
                 true,
-                std::u64::MAX,
+                u64::MAX,
             ).expect("Could not wait for fence");
 
             self.raw.reset_fences(
                 &[frame0.main_cb.submit_done_fence],
             ).expect("Could not reset fence");
+
+            frame0.descriptor_allocator.get_mut().unwrap().reset();
         }
         frame0.clone()
     }
@@ -39,6 +52,8 @@ impl BeginFrame for RenderDevice{
 
             std::mem::swap(frame0, frame1);
         }
+
+        self.shared.frame_index.fetch_add(1, Ordering::Relaxed);
     }
 
     fn submit_frame(&self, submits: &[vk::SubmitInfo], frame: &DeviceFrame) {
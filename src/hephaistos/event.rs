@@ -0,0 +1,119 @@
+use super::*;
+use ash::vk;
+use vk_sync::AccessType;
+
+// Split barriers: set_event records the first half of the barriers right after the last previous
+// access, wait_event records the second half right before the first next access, so unrelated
+// work can overlap in between. Both calls have to be given the same barriers.
+
+pub trait CreateEvent {
+    fn create_event(&self) -> Event;
+}
+
+impl CreateEvent for Arc<SharedDevice> {
+    fn create_event(&self) -> Event {
+        // Events are only set and waited on in command buffers, never from the host.
+        let flags = match self.synchronization2 {
+            Some(_) => vk::EventCreateFlags::DEVICE_ONLY,
+            None => vk::EventCreateFlags::empty(),
+        };
+
+        let raw = unsafe {
+            self.raw
+                .create_event(&vk::EventCreateInfo::builder().flags(flags), None)
+                .expect("Could not create event")
+        };
+
+        Event {
+            raw,
+            device: self.clone(),
+        }
+    }
+}
+
+pub fn set_event(
+    device: &SharedDevice,
+    cb: vk::CommandBuffer,
+    event: &Event,
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) {
+    match &device.synchronization2 {
+        Some(synchronization2) => {
+            let barriers = get_barriers2(global_barrier, buffer_barriers, image_barriers);
+            unsafe {
+                synchronization2.cmd_set_event2(cb, event.raw, &barriers.dependency_info());
+            }
+        }
+        None => {
+            let barriers = get_legacy_barriers(global_barrier, buffer_barriers, image_barriers);
+            unsafe {
+                device.cmd_set_event(cb, event.raw, barriers.src_stage_mask);
+            }
+        }
+    }
+}
+
+pub fn wait_event(
+    device: &SharedDevice,
+    cb: vk::CommandBuffer,
+    event: &Event,
+    global_barrier: Option<&GlobalBarrier>,
+    buffer_barriers: &[BufferBarrier],
+    image_barriers: &[ImageBarrier],
+) {
+    // The dependency only exists once the wait is recorded.
+    if let Some(hazard_checker) = &device.hazard_checker {
+        hazard_checker.lock().unwrap().record_barriers(cb, global_barrier, buffer_barriers, image_barriers);
+    }
+
+    match &device.synchronization2 {
+        Some(synchronization2) => {
+            let barriers = get_barriers2(global_barrier, buffer_barriers, image_barriers);
+            unsafe {
+                synchronization2.cmd_wait_events2(cb, &[event.raw], &[barriers.dependency_info().build()]);
+            }
+        }
+        None => {
+            let barriers = get_legacy_barriers(global_barrier, buffer_barriers, image_barriers);
+            unsafe {
+                device.cmd_wait_events(
+                    cb,
+                    &[event.raw],
+                    barriers.src_stage_mask,
+                    barriers.dst_stage_mask,
+                    &barriers.memory_barriers,
+                    &barriers.buffer_memory_barriers,
+                    &barriers.image_memory_barriers,
+                );
+            }
+        }
+    }
+}
+
+// Unsignals the event once the accesses that waited on it are done, so it can be set again.
+pub fn reset_event(device: &SharedDevice, cb: vk::CommandBuffer, event: &Event, accesses: &[AccessType]) {
+    match &device.synchronization2 {
+        Some(synchronization2) => unsafe {
+            synchronization2.cmd_reset_event2(cb, event.raw, stage_mask2(accesses));
+        },
+        None => {
+            let mut stage_mask = stage_mask(accesses);
+            if stage_mask.is_empty() {
+                stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+            }
+            unsafe {
+                device.cmd_reset_event(cb, event.raw, stage_mask);
+            }
+        }
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_event(self.raw, None);
+        }
+    }
+}
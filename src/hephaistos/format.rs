@@ -0,0 +1,73 @@
+use ash::vk;
+
+// How shaders read and write a format, e.g. integer attachments have to be cleared with
+// integer values and vertex attributes have to match the shader input type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatNumericType{
+    Float,
+    Sint,
+    Uint,
+}
+
+// Normalized, scaled, float and sRGB formats all read as floats.
+pub fn format_numeric_type(format: vk::Format) -> FormatNumericType{
+    use vk::Format as F;
+    match format{
+        F::R8_UINT | F::R8G8_UINT | F::R8G8B8_UINT | F::B8G8R8_UINT | F::R8G8B8A8_UINT
+        | F::B8G8R8A8_UINT | F::A8B8G8R8_UINT_PACK32 | F::A2R10G10B10_UINT_PACK32
+        | F::A2B10G10R10_UINT_PACK32 | F::R16_UINT | F::R16G16_UINT | F::R16G16B16_UINT
+        | F::R16G16B16A16_UINT | F::R32_UINT | F::R32G32_UINT | F::R32G32B32_UINT
+        | F::R32G32B32A32_UINT | F::R64_UINT | F::R64G64_UINT | F::R64G64B64_UINT
+        | F::R64G64B64A64_UINT | F::S8_UINT => FormatNumericType::Uint,
+        F::R8_SINT | F::R8G8_SINT | F::R8G8B8_SINT | F::B8G8R8_SINT | F::R8G8B8A8_SINT
+        | F::B8G8R8A8_SINT | F::A8B8G8R8_SINT_PACK32 | F::A2R10G10B10_SINT_PACK32
+        | F::A2B10G10R10_SINT_PACK32 | F::R16_SINT | F::R16G16_SINT | F::R16G16B16_SINT
+        | F::R16G16B16A16_SINT | F::R32_SINT | F::R32G32_SINT | F::R32G32B32_SINT
+        | F::R32G32B32A32_SINT | F::R64_SINT | F::R64G64_SINT | F::R64G64B64_SINT
+        | F::R64G64B64A64_SINT => FormatNumericType::Sint,
+        _ => FormatNumericType::Float,
+    }
+}
+
+// Size of one element of a vertex buffer format, None for formats that cannot be used in vertex buffers
+// (block compressed, depth/stencil and multi-planar formats).
+pub fn format_size(format: vk::Format) -> Option<u32>{
+    use vk::Format as F;
+    match format{
+        F::R4G4_UNORM_PACK8 | F::R8_UNORM | F::R8_SNORM | F::R8_USCALED | F::R8_SSCALED | F::R8_UINT
+        | F::R8_SINT | F::R8_SRGB => Some(1),
+        F::R8G8_UNORM | F::R8G8_SNORM | F::R8G8_USCALED | F::R8G8_SSCALED | F::R8G8_UINT
+        | F::R8G8_SINT | F::R8G8_SRGB | F::R16_UNORM | F::R16_SNORM | F::R16_USCALED
+        | F::R16_SSCALED | F::R16_UINT | F::R16_SINT | F::R16_SFLOAT | F::R4G4B4A4_UNORM_PACK16
+        | F::B4G4R4A4_UNORM_PACK16 | F::R5G6B5_UNORM_PACK16 | F::B5G6R5_UNORM_PACK16
+        | F::R5G5B5A1_UNORM_PACK16 | F::B5G5R5A1_UNORM_PACK16 | F::A1R5G5B5_UNORM_PACK16 => Some(2),
+        F::R8G8B8_UNORM | F::R8G8B8_SNORM | F::R8G8B8_USCALED | F::R8G8B8_SSCALED | F::R8G8B8_UINT
+        | F::R8G8B8_SINT | F::R8G8B8_SRGB | F::B8G8R8_UNORM | F::B8G8R8_SNORM | F::B8G8R8_USCALED
+        | F::B8G8R8_SSCALED | F::B8G8R8_UINT | F::B8G8R8_SINT | F::B8G8R8_SRGB => Some(3),
+        F::R8G8B8A8_UNORM | F::R8G8B8A8_SNORM | F::R8G8B8A8_USCALED | F::R8G8B8A8_SSCALED
+        | F::R8G8B8A8_UINT | F::R8G8B8A8_SINT | F::R8G8B8A8_SRGB | F::B8G8R8A8_UNORM
+        | F::B8G8R8A8_SNORM | F::B8G8R8A8_USCALED | F::B8G8R8A8_SSCALED | F::B8G8R8A8_UINT
+        | F::B8G8R8A8_SINT | F::B8G8R8A8_SRGB | F::A8B8G8R8_UNORM_PACK32 | F::A8B8G8R8_SNORM_PACK32
+        | F::A8B8G8R8_USCALED_PACK32 | F::A8B8G8R8_SSCALED_PACK32 | F::A8B8G8R8_UINT_PACK32
+        | F::A8B8G8R8_SINT_PACK32 | F::A8B8G8R8_SRGB_PACK32 | F::A2R10G10B10_UNORM_PACK32
+        | F::A2R10G10B10_SNORM_PACK32 | F::A2R10G10B10_USCALED_PACK32
+        | F::A2R10G10B10_SSCALED_PACK32 | F::A2R10G10B10_UINT_PACK32 | F::A2R10G10B10_SINT_PACK32
+        | F::A2B10G10R10_UNORM_PACK32 | F::A2B10G10R10_SNORM_PACK32 | F::A2B10G10R10_USCALED_PACK32
+        | F::A2B10G10R10_SSCALED_PACK32 | F::A2B10G10R10_UINT_PACK32 | F::A2B10G10R10_SINT_PACK32
+        | F::B10G11R11_UFLOAT_PACK32 | F::E5B9G9R9_UFLOAT_PACK32 | F::R16G16_UNORM | F::R16G16_SNORM
+        | F::R16G16_USCALED | F::R16G16_SSCALED | F::R16G16_UINT | F::R16G16_SINT | F::R16G16_SFLOAT
+        | F::R32_UINT | F::R32_SINT | F::R32_SFLOAT => Some(4),
+        F::R16G16B16_UNORM | F::R16G16B16_SNORM | F::R16G16B16_USCALED | F::R16G16B16_SSCALED
+        | F::R16G16B16_UINT | F::R16G16B16_SINT | F::R16G16B16_SFLOAT => Some(6),
+        F::R16G16B16A16_UNORM | F::R16G16B16A16_SNORM | F::R16G16B16A16_USCALED
+        | F::R16G16B16A16_SSCALED | F::R16G16B16A16_UINT | F::R16G16B16A16_SINT
+        | F::R16G16B16A16_SFLOAT | F::R32G32_UINT | F::R32G32_SINT | F::R32G32_SFLOAT | F::R64_UINT
+        | F::R64_SINT | F::R64_SFLOAT => Some(8),
+        F::R32G32B32_UINT | F::R32G32B32_SINT | F::R32G32B32_SFLOAT => Some(12),
+        F::R32G32B32A32_UINT | F::R32G32B32A32_SINT | F::R32G32B32A32_SFLOAT | F::R64G64_UINT
+        | F::R64G64_SINT | F::R64G64_SFLOAT => Some(16),
+        F::R64G64B64_UINT | F::R64G64B64_SINT | F::R64G64B64_SFLOAT => Some(24),
+        F::R64G64B64A64_UINT | F::R64G64B64A64_SINT | F::R64G64B64A64_SFLOAT => Some(32),
+        _ => None,
+    }
+}
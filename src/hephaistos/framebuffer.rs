@@ -28,22 +28,20 @@ DEALINGS IN THE SOFTWARE.
  */
 
 use super::*;
-use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
-use raw_window_handle::HasRawWindowHandle;
-use std::borrow::BorrowMut;
-use std::sync::Arc;
+use ash::vk;
+use std::sync::atomic::Ordering;
 
 impl FramebufferCacheKey {
     pub fn new<'a>(
         extent: vk::Extent2D,
         color_attachments: impl Iterator<Item = &'a FramebufferAttachmentDesc>,
         depth_stencil_attachment: Option<&'a FramebufferAttachmentDesc>,
+        resolve_attachments: impl Iterator<Item = &'a FramebufferAttachmentDesc>,
     ) -> Self {
         let color_attachments = color_attachments
-            .chain(depth_stencil_attachment.into_iter())
+            .chain(depth_stencil_attachment)
+            .chain(resolve_attachments)
             .copied()
-            .map(|attachment| attachment)
             .collect();
 
         Self {
@@ -72,7 +70,9 @@ impl FramebufferCache {
     pub fn new(
         render_pass: vk::RenderPass,
         color_attachments: &[vk::AttachmentDescription],
-        depth_attachment: Option<vk::AttachmentDescription>,
+        depth_attachment: Option<DepthStencilAttachmentDesc>,
+        resolve_attachments: &[vk::AttachmentDescription],
+        multiview: bool,
     ) -> Self {
         let mut attachment_desc = ArrayVec::new();
 
@@ -81,28 +81,50 @@ impl FramebufferCache {
             .unwrap();
 
         if let Some(depth_attachment) = depth_attachment {
-            attachment_desc.push(depth_attachment)
+            attachment_desc.push(depth_attachment.into())
         }
 
+        attachment_desc
+            .try_extend_from_slice(resolve_attachments)
+            .unwrap();
+
         Self {
-            entries: Default::default(),
+            state: Default::default(),
             attachment_desc,
             render_pass,
-            color_attachment_count: color_attachments.len(),
+            multiview,
         }
     }
     pub fn get_or_create(
         &self,
-        device: &ash::Device,
+        device: &SharedDevice,
         key: FramebufferCacheKey,
     ) -> Option<vk::Framebuffer> {
-        let mut entries = self.entries.lock().unwrap();
+        let frame_index = device.frame_index.load(Ordering::Relaxed);
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        if state.last_eviction_frame != frame_index {
+            state.last_eviction_frame = frame_index;
+            Self::destroy_retired(device, state, frame_index);
+
+            let expired = state.entries
+                .iter()
+                .filter(|(_, entry)| frame_index - entry.last_used_frame > FRAMEBUFFER_CACHE_MAX_AGE)
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            for key in expired {
+                Self::retire(state, &key);
+            }
+        }
 
-        if let Some(entry) = entries.get(&key) {
-            Some(*entry)
+        if let Some(entry) = state.entries.get_mut(&key) {
+            entry.last_used_frame = frame_index;
+            state.stats.hits += 1;
+            Some(entry.framebuffer)
         } else {
-            let entry = {
-                let attachments: ArrayVec<_, MAX_COLOR_ATTACHMENTS> = self
+            let framebuffer = {
+                let attachments: ArrayVec<_, MAX_ATTACHMENTS> = self
                     .attachment_desc
                     .iter()
                     .zip(key.attachments.iter())
@@ -118,6 +140,17 @@ impl FramebufferCache {
                     })
                     .collect();
 
+                // Layered rendering, e.g. to all faces of a cube or a shadow map array, selects the
+                // layer in the shader and is limited by the attachment with the fewest layers.
+                let layers = match self.multiview {
+                    true => 1,
+                    false => key.attachments
+                        .iter()
+                        .map(|attachment| attachment.layer_count)
+                        .min()
+                        .unwrap_or(1),
+                };
+
                 let mut imageless_desc = vk::FramebufferAttachmentsCreateInfo::builder()
                     .attachment_image_infos(&attachments);
                 let mut create_info = vk::FramebufferCreateInfo::builder()
@@ -125,7 +158,7 @@ impl FramebufferCache {
                     .render_pass(self.render_pass)
                     .width(key.extent.width)
                     .height(key.extent.height)
-                    .layers(1)
+                    .layers(layers)
                     .push_next(&mut imageless_desc);
 
                 create_info.attachment_count = attachments.len() as u32;
@@ -133,15 +166,62 @@ impl FramebufferCache {
                 unsafe { device.create_framebuffer(&create_info, None).unwrap() }
             };
 
-            entries.insert(key, entry);
-            Some(entry)
+            if state.entries.len() >= FRAMEBUFFER_CACHE_CAPACITY {
+                let least_recently_used = state.entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used_frame)
+                    .map(|(key, _)| key.clone());
+                if let Some(key) = least_recently_used {
+                    Self::retire(state, &key);
+                }
+            }
+
+            state.stats.misses += 1;
+            state.entries.insert(key, FramebufferCacheEntry {
+                framebuffer,
+                last_used_frame: frame_index,
+            });
+            Some(framebuffer)
+        }
+    }
+    fn retire(state: &mut FramebufferCacheState, key: &FramebufferCacheKey) {
+        if let Some(entry) = state.entries.remove(key) {
+            state.stats.evictions += 1;
+            state.retired.push(entry);
+        }
+    }
+    // A framebuffer can be destroyed once the last frame using it has been waited on.
+    fn destroy_retired(device: &SharedDevice, state: &mut FramebufferCacheState, frame_index: u64) {
+        state.retired.retain(|entry| {
+            let in_flight = entry.last_used_frame + FRAMES_IN_FLIGHT as u64 > frame_index;
+            if !in_flight {
+                unsafe { device.destroy_framebuffer(entry.framebuffer, None) };
+            }
+            in_flight
+        });
+    }
+    // Has to be called when attachments are recreated, e.g. after the swapchain was resized.
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        let keys = state.entries.keys().cloned().collect::<Vec<_>>();
+        for key in keys {
+            Self::retire(&mut state, &key);
+        }
+    }
+    pub fn stats(&self) -> FramebufferCacheStats {
+        let state = self.state.lock().unwrap();
+        FramebufferCacheStats {
+            entries: state.entries.len(),
+            ..state.stats
         }
     }
     pub fn destroy_cache(&self, device: &ash::Device){
-        for (_, item) in self.entries.lock().unwrap().iter(){
-            unsafe{device.destroy_framebuffer(*item, None)};
+        let mut state = self.state.lock().unwrap();
+        for (_, entry) in state.entries.drain(){
+            unsafe{device.destroy_framebuffer(entry.framebuffer, None)};
+        }
+        for entry in state.retired.drain(..){
+            unsafe{device.destroy_framebuffer(entry.framebuffer, None)};
         }
-        self.entries.lock().unwrap().clear();
     }
 }
-
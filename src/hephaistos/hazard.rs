@@ -0,0 +1,530 @@
+use super::*;
+use ash::vk;
+use std::fmt;
+use vk_sync::AccessType;
+
+// Pure CPU model of the accesses and barriers the library records, used in debug builds to
+// catch synchronization errors without relying on a driver or validation layers. Hazards are
+// logged as warnings and collected, see HazardChecker::take_hazards.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceId{
+    Image(vk::Image),
+    Buffer(vk::Buffer),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HazardKind{
+    ReadAfterWrite,
+    WriteAfterWrite,
+    WriteAfterRead,
+    WrongLayout{
+        expected: vk::ImageLayout,
+        actual: vk::ImageLayout,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Hazard{
+    pub kind: HazardKind,
+    pub resource: String,
+    pub scope: Option<String>,
+    pub command_buffer: vk::CommandBuffer,
+    pub prev_access: AccessType,
+    pub next_access: AccessType,
+}
+
+impl fmt::Display for Hazard{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind{
+            HazardKind::ReadAfterWrite => write!(f, "read-after-write hazard")?,
+            HazardKind::WriteAfterWrite => write!(f, "write-after-write hazard")?,
+            HazardKind::WriteAfterRead => write!(f, "write-after-read hazard")?,
+            HazardKind::WrongLayout{expected, actual} => write!(
+                f,
+                "wrong layout (expected {:?}, image is in {:?})",
+                expected,
+                actual,
+            )?,
+        }
+        write!(f, " on {}", self.resource)?;
+        if let Some(scope) = &self.scope{
+            write!(f, " in \"{}\"", scope)?;
+        }
+        write!(f, ": {:?} -> {:?}", self.prev_access, self.next_access)
+    }
+}
+
+#[derive(Default)]
+struct ResourceState{
+    // None until a barrier or access establishes the layout within the command buffer.
+    layout: Option<vk::ImageLayout>,
+    unsynchronized_write: Option<AccessType>,
+    unsynchronized_reads: Vec<AccessType>,
+    // Write made available by a barrier, and the stages it was made visible to.
+    synchronized_write: Option<(AccessType, vk::PipelineStageFlags)>,
+}
+
+#[derive(Default)]
+struct CommandBufferState{
+    resources: FxHashMap<ResourceId, ResourceState>,
+    scope: Option<String>,
+}
+
+#[derive(Default)]
+pub struct HazardChecker{
+    names: FxHashMap<ResourceId, String>,
+    command_buffers: FxHashMap<vk::CommandBuffer, CommandBufferState>,
+    hazards: Vec<Hazard>,
+}
+
+fn stages_cover(stage_mask: vk::PipelineStageFlags, stages: vk::PipelineStageFlags) -> bool{
+    stage_mask.contains(vk::PipelineStageFlags::ALL_COMMANDS) || stage_mask.contains(stages)
+}
+
+impl HazardChecker{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    pub fn set_name(&mut self, resource: ResourceId, name: &str){
+        self.names.insert(resource, name.to_string());
+    }
+
+    pub fn name(&self, resource: ResourceId) -> String{
+        match self.names.get(&resource){
+            Some(name) => name.clone(),
+            None => format!("{:?}", resource),
+        }
+    }
+
+    // Forgets everything recorded for the command buffer, called when recording begins.
+    pub fn begin_command_buffer(&mut self, cb: vk::CommandBuffer){
+        self.command_buffers.insert(cb, CommandBufferState::default());
+    }
+
+    // Names the scope (e.g. render graph pass) reported with hazards found in it.
+    pub fn set_scope(&mut self, cb: vk::CommandBuffer, scope: Option<&str>){
+        self.command_buffers.entry(cb).or_default().scope = scope.map(str::to_string);
+    }
+
+    pub fn hazards(&self) -> &[Hazard]{
+        &self.hazards
+    }
+
+    pub fn take_hazards(&mut self) -> Vec<Hazard>{
+        std::mem::take(&mut self.hazards)
+    }
+
+    fn report(&mut self, cb: vk::CommandBuffer, resource: ResourceId, kind: HazardKind, prev_access: AccessType, next_access: AccessType){
+        let hazard = Hazard{
+            kind,
+            resource: self.name(resource),
+            scope: self.command_buffers.get(&cb).and_then(|state| state.scope.clone()),
+            command_buffer: cb,
+            prev_access,
+            next_access,
+        };
+        log::warn!("Synchronization hazard: {}", hazard);
+        self.hazards.push(hazard);
+    }
+
+    pub fn record_access(&mut self, cb: vk::CommandBuffer, resource: ResourceId, access: AccessType){
+        self.record_accesses(cb, resource, &[access]);
+    }
+
+    // Accesses recorded together (e.g. by one render graph pass) are not checked against each other.
+    pub fn record_accesses(&mut self, cb: vk::CommandBuffer, resource: ResourceId, accesses: &[AccessType]){
+        let mut found = Vec::new();
+        {
+            let state = self.command_buffers
+                .entry(cb)
+                .or_default()
+                .resources
+                .entry(resource)
+                .or_default();
+
+            for access in accesses.iter().copied(){
+                let info = get_access_info(access);
+                let is_write = is_write_access(access);
+
+                let write_kind = match is_write{
+                    true => HazardKind::WriteAfterWrite,
+                    false => HazardKind::ReadAfterWrite,
+                };
+
+                if let Some(prev_access) = state.unsynchronized_write{
+                    found.push((write_kind, prev_access, access));
+                } else if let Some((prev_access, visible_stages)) = state.synchronized_write{
+                    if !stages_cover(visible_stages, info.stage_mask){
+                        found.push((write_kind, prev_access, access));
+                    }
+                }
+
+                if is_write{
+                    if let Some(prev_access) = state.unsynchronized_reads.first(){
+                        found.push((HazardKind::WriteAfterRead, *prev_access, access));
+                    }
+                }
+
+                if let (ResourceId::Image(_), Some(layout)) = (resource, state.layout){
+                    if info.image_layout != vk::ImageLayout::UNDEFINED && info.image_layout != layout{
+                        let kind = HazardKind::WrongLayout{
+                            expected: info.image_layout,
+                            actual: layout,
+                        };
+                        found.push((kind, access, access));
+                    }
+                }
+            }
+
+            match accesses.iter().rev().find(|access| is_write_access(**access)){
+                Some(write) => {
+                    state.unsynchronized_write = Some(*write);
+                    state.unsynchronized_reads.clear();
+                    state.synchronized_write = None;
+                }
+                None => {
+                    state.unsynchronized_reads.extend_from_slice(accesses);
+                }
+            }
+        }
+
+        for (kind, prev_access, next_access) in found{
+            self.report(cb, resource, kind, prev_access, next_access);
+        }
+    }
+
+    fn record_barrier(
+        &mut self,
+        cb: vk::CommandBuffer,
+        resource: ResourceId,
+        prev_accesses: &[AccessType],
+        next_accesses: &[AccessType],
+        discard: bool,
+    ){
+        let src_stage_mask = stage_mask(prev_accesses);
+        let dst_stage_mask = stage_mask(next_accesses);
+        let src_access_mask = prev_accesses
+            .iter()
+            .filter(|access| is_write_access(**access))
+            .fold(vk::AccessFlags::empty(), |mask, access| mask | get_access_info(*access).access_mask);
+
+        let mut wrong_layout = None;
+        {
+            let state = self.command_buffers
+                .entry(cb)
+                .or_default()
+                .resources
+                .entry(resource)
+                .or_default();
+
+            if discard{
+                *state = ResourceState::default();
+            }
+
+            if let Some(write) = state.unsynchronized_write{
+                let write_info = get_access_info(write);
+                let available = stages_cover(src_stage_mask, write_info.stage_mask)
+                    && (src_access_mask.contains(vk::AccessFlags::MEMORY_WRITE)
+                        || src_access_mask.contains(write_info.access_mask));
+                if available{
+                    state.unsynchronized_write = None;
+                    state.synchronized_write = Some((write, dst_stage_mask));
+                }
+            } else if let Some((write, visible_stages)) = state.synchronized_write{
+                state.synchronized_write = Some((write, visible_stages | dst_stage_mask));
+            }
+
+            state.unsynchronized_reads.retain(|read| {
+                !stages_cover(src_stage_mask, get_access_info(*read).stage_mask)
+            });
+
+            if let ResourceId::Image(_) = resource{
+                let old_layout = image_layout(prev_accesses);
+                if let Some(layout) = state.layout{
+                    if !discard && old_layout != vk::ImageLayout::UNDEFINED && old_layout != layout{
+                        wrong_layout = Some(HazardKind::WrongLayout{
+                            expected: old_layout,
+                            actual: layout,
+                        });
+                    }
+                }
+                state.layout = Some(image_layout(next_accesses));
+            }
+        }
+
+        if let Some(kind) = wrong_layout{
+            self.report(
+                cb,
+                resource,
+                kind,
+                prev_accesses.first().copied().unwrap_or(AccessType::Nothing),
+                next_accesses.first().copied().unwrap_or(AccessType::Nothing),
+            );
+        }
+    }
+
+    pub fn record_image_barrier(&mut self, cb: vk::CommandBuffer, barrier: &ImageBarrier){
+        self.record_barrier(
+            cb,
+            ResourceId::Image(barrier.image),
+            barrier.prev_accesses,
+            barrier.next_accesses,
+            barrier.discard,
+        );
+    }
+
+    pub fn record_buffer_barrier(&mut self, cb: vk::CommandBuffer, barrier: &BufferBarrier){
+        self.record_barrier(
+            cb,
+            ResourceId::Buffer(barrier.buffer),
+            barrier.prev_accesses,
+            barrier.next_accesses,
+            false,
+        );
+    }
+
+    pub fn record_barriers(
+        &mut self,
+        cb: vk::CommandBuffer,
+        global_barrier: Option<&GlobalBarrier>,
+        buffer_barriers: &[BufferBarrier],
+        image_barriers: &[ImageBarrier],
+    ){
+        if let Some(barrier) = global_barrier{
+            self.record_global_barrier(cb, barrier);
+        }
+        for barrier in buffer_barriers{
+            self.record_buffer_barrier(cb, barrier);
+        }
+        for barrier in image_barriers{
+            self.record_image_barrier(cb, barrier);
+        }
+    }
+
+    // Global barriers synchronize memory of every resource but cannot change layouts.
+    pub fn record_global_barrier(&mut self, cb: vk::CommandBuffer, barrier: &GlobalBarrier){
+        let resources = match self.command_buffers.get(&cb){
+            Some(state) => state.resources.keys().copied().collect::<Vec<_>>(),
+            None => return,
+        };
+        for resource in resources{
+            let layout = self.command_buffers[&cb].resources[&resource].layout;
+            self.record_barrier(cb, resource, barrier.prev_accesses, barrier.next_accesses, false);
+            if let Some(state) = self.command_buffers.get_mut(&cb).and_then(|state| state.resources.get_mut(&resource)){
+                state.layout = layout;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use ash::vk::Handle;
+
+    fn cb() -> vk::CommandBuffer{
+        vk::CommandBuffer::from_raw(3)
+    }
+    fn image() -> ResourceId{
+        ResourceId::Image(vk::Image::from_raw(1))
+    }
+    fn buffer() -> ResourceId{
+        ResourceId::Buffer(vk::Buffer::from_raw(2))
+    }
+
+    fn checker() -> HazardChecker{
+        let mut checker = HazardChecker::new();
+        checker.begin_command_buffer(cb());
+        checker
+    }
+
+    fn kinds(checker: &mut HazardChecker) -> Vec<HazardKind>{
+        checker.take_hazards().into_iter().map(|hazard| hazard.kind).collect()
+    }
+
+    fn image_barrier(checker: &mut HazardChecker, prev: &[AccessType], next: &[AccessType], discard: bool){
+        checker.record_image_barrier(
+            cb(),
+            &ImageBarrier::new(vk::Image::from_raw(1), prev, next, vk::ImageAspectFlags::COLOR).with_discard(discard),
+        );
+    }
+
+    fn buffer_barrier(checker: &mut HazardChecker, prev: &[AccessType], next: &[AccessType]){
+        checker.record_buffer_barrier(cb(), &BufferBarrier::new(vk::Buffer::from_raw(2), prev, next));
+    }
+
+    #[test]
+    fn read_after_write(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        assert_eq!(kinds(&mut checker), [HazardKind::ReadAfterWrite]);
+    }
+
+    #[test]
+    fn write_after_write(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderWrite);
+        assert_eq!(kinds(&mut checker), [HazardKind::WriteAfterWrite]);
+    }
+
+    #[test]
+    fn write_after_read(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        assert_eq!(kinds(&mut checker), [HazardKind::WriteAfterRead]);
+    }
+
+    #[test]
+    fn accesses_of_one_group_are_not_checked_against_each_other(){
+        let mut checker = checker();
+        checker.record_accesses(cb(), buffer(), &[AccessType::ComputeShaderReadOther, AccessType::ComputeShaderWrite]);
+        assert_eq!(kinds(&mut checker), []);
+    }
+
+    #[test]
+    fn barrier_synchronizes_write(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        buffer_barrier(&mut checker, &[AccessType::TransferWrite], &[AccessType::ComputeShaderReadOther]);
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        assert_eq!(kinds(&mut checker), []);
+    }
+
+    #[test]
+    fn barrier_src_stage_does_not_cover_write(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        buffer_barrier(&mut checker, &[AccessType::ComputeShaderWrite], &[AccessType::ComputeShaderReadOther]);
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        assert_eq!(kinds(&mut checker), [HazardKind::ReadAfterWrite]);
+    }
+
+    #[test]
+    fn barrier_src_access_does_not_cover_write(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        // Same stage, but a read access does not make the write available.
+        buffer_barrier(&mut checker, &[AccessType::TransferRead], &[AccessType::ComputeShaderReadOther]);
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        assert_eq!(kinds(&mut checker), [HazardKind::ReadAfterWrite]);
+    }
+
+    #[test]
+    fn barrier_dst_stage_does_not_cover_read(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        buffer_barrier(&mut checker, &[AccessType::TransferWrite], &[AccessType::VertexShaderReadOther]);
+        checker.record_access(cb(), buffer(), AccessType::VertexShaderReadOther);
+        checker.record_access(cb(), buffer(), AccessType::FragmentShaderReadOther);
+        assert_eq!(kinds(&mut checker), [HazardKind::ReadAfterWrite]);
+    }
+
+    #[test]
+    fn barrier_synchronizes_write_after_read(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        buffer_barrier(&mut checker, &[AccessType::ComputeShaderReadOther], &[AccessType::TransferWrite]);
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        assert_eq!(kinds(&mut checker), []);
+    }
+
+    #[test]
+    fn access_in_wrong_layout(){
+        let mut checker = checker();
+        image_barrier(&mut checker, &[AccessType::Nothing], &[AccessType::TransferWrite], false);
+        checker.record_access(cb(), image(), AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer);
+        assert_eq!(kinds(&mut checker), [HazardKind::WrongLayout{
+            expected: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            actual: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        }]);
+    }
+
+    #[test]
+    fn barrier_from_wrong_layout(){
+        let mut checker = checker();
+        image_barrier(&mut checker, &[AccessType::Nothing], &[AccessType::TransferWrite], false);
+        image_barrier(
+            &mut checker,
+            &[AccessType::ColorAttachmentWrite],
+            &[AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer],
+            false,
+        );
+        assert_eq!(kinds(&mut checker), [HazardKind::WrongLayout{
+            expected: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            actual: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        }]);
+    }
+
+    #[test]
+    fn discard_forgets_layout_and_writes(){
+        let record = |discard: bool| {
+            let mut checker = checker();
+            image_barrier(&mut checker, &[AccessType::Nothing], &[AccessType::TransferWrite], false);
+            checker.record_access(cb(), image(), AccessType::TransferWrite);
+            image_barrier(&mut checker, &[AccessType::ColorAttachmentWrite], &[AccessType::ColorAttachmentWrite], discard);
+            checker.record_access(cb(), image(), AccessType::ColorAttachmentWrite);
+            kinds(&mut checker)
+        };
+
+        assert_eq!(record(true), []);
+        assert_eq!(record(false), [
+            HazardKind::WrongLayout{
+                expected: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                actual: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            },
+            HazardKind::WriteAfterWrite,
+        ]);
+    }
+
+    #[test]
+    fn global_barrier_synchronizes_memory_but_keeps_layouts(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        image_barrier(&mut checker, &[AccessType::Nothing], &[AccessType::TransferWrite], false);
+        checker.record_access(cb(), image(), AccessType::TransferWrite);
+
+        checker.record_global_barrier(cb(), &GlobalBarrier::new(
+            &[AccessType::TransferWrite],
+            &[AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer],
+        ));
+        checker.record_access(cb(), buffer(), AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer);
+        checker.record_access(cb(), image(), AccessType::FragmentShaderReadSampledImageOrUniformTexelBuffer);
+
+        assert_eq!(kinds(&mut checker), [HazardKind::WrongLayout{
+            expected: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            actual: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        }]);
+    }
+
+    #[test]
+    fn hazards_are_collected_with_names_and_scope(){
+        let mut checker = checker();
+        checker.set_name(buffer(), "vertices");
+        checker.set_scope(cb(), Some("upload"));
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        checker.record_access(cb(), buffer(), AccessType::VertexBuffer);
+
+        let hazards = checker.take_hazards();
+        assert_eq!(hazards.len(), 1);
+        assert_eq!(hazards[0].resource, "vertices");
+        assert_eq!(hazards[0].scope.as_deref(), Some("upload"));
+        assert_eq!(hazards[0].prev_access, AccessType::TransferWrite);
+        assert_eq!(hazards[0].next_access, AccessType::VertexBuffer);
+        assert!(checker.hazards().is_empty());
+    }
+
+    #[test]
+    fn begin_command_buffer_forgets_previous_recording(){
+        let mut checker = checker();
+        checker.record_access(cb(), buffer(), AccessType::TransferWrite);
+        checker.begin_command_buffer(cb());
+        checker.record_access(cb(), buffer(), AccessType::ComputeShaderReadOther);
+        assert_eq!(kinds(&mut checker), []);
+    }
+}
+
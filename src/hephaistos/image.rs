@@ -1,11 +1,8 @@
 use super::*;
-use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
+use ash::vk;
 use gpu_allocator::vulkan::AllocationCreateDesc;
 use gpu_allocator::MemoryLocation;
-use raw_window_handle::HasRawWindowHandle;
-use std::borrow::BorrowMut;
-use std::sync::Arc;
+use vk_sync::AccessType;
 
 pub trait CreateImage {
     fn create_image(&self, desc: &ImageDesc, data: Vec<ImageSubresourceData>) -> Image;
@@ -14,7 +11,13 @@ pub trait CreateImage {
 impl CreateImage for RenderDevice{
     fn create_image(&self, desc: &ImageDesc, data: Vec<ImageSubresourceData>) -> Image {
         unsafe {
-            let create_info = get_image_create_info(desc, !data.is_empty());
+            let mut create_info = get_image_create_info(desc, !data.is_empty());
+            let queue_family_indices = self.concurrent_queue_family_indices();
+            if let Some(queue_family_indices) = &queue_family_indices {
+                create_info.sharing_mode = vk::SharingMode::CONCURRENT;
+                create_info.queue_family_index_count = queue_family_indices.len() as u32;
+                create_info.p_queue_family_indices = queue_family_indices.as_ptr();
+            }
 
             let image = self
                 .raw
@@ -39,6 +42,15 @@ impl CreateImage for RenderDevice{
                 .bind_image_memory(image, allocation.memory(), allocation.offset())
                 .expect("Image bind error");
 
+            let image = Image {
+                raw: image,
+                desc: *desc,
+                views: Mutex::new(FxHashMap::default()),
+                access: Mutex::new(vec![AccessType::Nothing; desc.subresource_count()]),
+                allocation: Some(allocation),
+                device: self.shared.clone(),
+            };
+
             // TODO: load image into memory.
             if !data.is_empty(){
                 let data_bytes: usize = data.iter().map(|d| d.data.len()).sum();
@@ -79,168 +91,334 @@ impl CreateImage for RenderDevice{
                 .collect::<Vec<_>>();
 
                 self.with_setup_cb(|cb| {
-                    let barrier = vk::ImageMemoryBarrier{
-                        dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-                        new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        image,
-                        subresource_range: vk::ImageSubresourceRange {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            base_mip_level: 0,
-                            level_count: vk::REMAINING_MIP_LEVELS,
-                            base_array_layer: 0,
-                            layer_count: vk::REMAINING_ARRAY_LAYERS,
-                        },
-                        ..Default::default()
-                    };
-
-                    self.raw.cmd_pipeline_barrier(
-                        cb,
-                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::DependencyFlags::empty(),
-                        &[],
-                        &[],
-                        &[barrier],
-                    );
+                    image.transition(cb, AccessType::TransferWrite);
 
                     self.raw.cmd_copy_buffer_to_image(
                         cb,
                         buffer.raw,
-                        image,
+                        image.raw,
                         vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                         &buffer_copy_regions,
                     );
 
-                    let barrier = vk::ImageMemoryBarrier{
-                        src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-                        dst_access_mask: vk::AccessFlags::SHADER_READ,
-                        new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        image,
-                        subresource_range: vk::ImageSubresourceRange {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            base_mip_level: 0,
-                            level_count: vk::REMAINING_MIP_LEVELS,
-                            base_array_layer: 0,
-                            layer_count: vk::REMAINING_ARRAY_LAYERS,
-                        },
-                        ..Default::default()
-                    };
-
-                    self.raw.cmd_pipeline_barrier(
-                        cb,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        vk::DependencyFlags::empty(),
-                        &[],
-                        &[],
-                        &[barrier],
-                    );
+                    image.transition(cb, AccessType::AnyShaderReadSampledImageOrUniformTexelBuffer);
                 });
             }
 
-            Image {
-                raw: image,
-                desc: *desc,
-                views: Mutex::new(FxHashMap::default()),
-                device: self.shared.clone(),
-            }
+            image
         }
     }
 }
 
 impl Image {
+    pub fn subresource_index(&self, mip_level: u32, array_layer: u32) -> usize {
+        (mip_level * self.desc.array_layers() + array_layer) as usize
+    }
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: image_aspect_mask_from_format(self.desc.format),
+            base_mip_level: 0,
+            level_count: self.desc.mip_levels,
+            base_array_layer: 0,
+            layer_count: self.desc.array_layers(),
+        }
+    }
+    // Resolves REMAINING_MIP_LEVELS and REMAINING_ARRAY_LAYERS against the image.
+    fn resolve_subresource_range(&self, range: vk::ImageSubresourceRange) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            level_count: match range.level_count {
+                vk::REMAINING_MIP_LEVELS => self.desc.mip_levels - range.base_mip_level,
+                level_count => level_count,
+            },
+            layer_count: match range.layer_count {
+                vk::REMAINING_ARRAY_LAYERS => self.desc.array_layers() - range.base_array_layer,
+                layer_count => layer_count,
+            },
+            ..range
+        }
+    }
+    pub fn current_access(&self, mip_level: u32, array_layer: u32) -> AccessType {
+        self.access.lock().unwrap()[self.subresource_index(mip_level, array_layer)]
+    }
+    // Some if every subresource is in the same access.
+    pub fn uniform_access(&self) -> Option<AccessType> {
+        let access = self.access.lock().unwrap();
+        match access.iter().all(|access_type| *access_type == access[0]) {
+            true => Some(access[0]),
+            false => None,
+        }
+    }
+    // Records an access without emitting a barrier, e.g. after a render pass
+    // transitioned the image to its final layout.
+    pub fn set_access(&self, range: vk::ImageSubresourceRange, access_type: AccessType) {
+        let range = self.resolve_subresource_range(range);
+        let mut access = self.access.lock().unwrap();
+        for level in range.base_mip_level..range.base_mip_level + range.level_count {
+            for layer in range.base_array_layer..range.base_array_layer + range.layer_count {
+                access[self.subresource_index(level, layer)] = access_type;
+            }
+        }
+    }
+    pub fn transition(&self, cb: vk::CommandBuffer, next_access: AccessType) {
+        self.transition_range(cb, self.subresource_range(), next_access)
+    }
+    pub fn transition_range(&self, cb: vk::CommandBuffer, range: vk::ImageSubresourceRange, next_access: AccessType) {
+        let range = self.resolve_subresource_range(range);
+
+        // Subresources of one mip level which share their previous access go into one barrier.
+        let mut runs = Vec::new();
+        {
+            let mut access = self.access.lock().unwrap();
+            let layer_end = range.base_array_layer + range.layer_count;
+            for level in range.base_mip_level..range.base_mip_level + range.level_count {
+                let mut layer = range.base_array_layer;
+                while layer < layer_end {
+                    let prev_access = access[self.subresource_index(level, layer)];
+                    let mut run_end = layer + 1;
+                    while run_end < layer_end && access[self.subresource_index(level, run_end)] == prev_access {
+                        run_end += 1;
+                    }
+                    for run_layer in layer..run_end {
+                        access[self.subresource_index(level, run_layer)] = next_access;
+                    }
+
+                    // Repeated reads with the same access need no synchronization.
+                    if prev_access != next_access || is_write_access(next_access) {
+                        runs.push((
+                            vk::ImageSubresourceRange {
+                                aspect_mask: range.aspect_mask,
+                                base_mip_level: level,
+                                level_count: 1,
+                                base_array_layer: layer,
+                                layer_count: run_end - layer,
+                            },
+                            prev_access,
+                        ));
+                    }
+
+                    layer = run_end;
+                }
+            }
+        }
+
+        let barriers = runs
+            .iter()
+            .map(|(range, prev_access)| {
+                ImageBarrier::new(
+                    self.raw,
+                    std::slice::from_ref(prev_access),
+                    std::slice::from_ref(&next_access),
+                    range.aspect_mask,
+                ).with_range(*range)
+            })
+            .collect::<Vec<_>>();
+
+        record_barriers(&self.device, cb, None, &[], &barriers);
+    }
+    pub fn view_subresource_range(&self, desc: &ImageViewDesc) -> vk::ImageSubresourceRange {
+        view_subresource_range(&self.desc, desc)
+    }
     pub fn get_view_create_info(&self, desc: &ImageViewDesc) -> vk::ImageViewCreateInfo {
         vk::ImageViewCreateInfo {
-            format: self.desc.format,
-            components: vk::ComponentMapping {
-                r: vk::ComponentSwizzle::R,
-                g: vk::ComponentSwizzle::G,
-                b: vk::ComponentSwizzle::B,
-                a: vk::ComponentSwizzle::A,
-            },
-            view_type: desc
-                .view_type
-                .unwrap_or_else(|| convert_image_type_to_view_type(self.desc.image_type)),
-                subresource_range: vk::ImageSubresourceRange {
-                    aspect_mask: desc.aspect_mask,
-                    base_mip_level: desc.base_mip_level,
-                    level_count: desc.level_count.unwrap_or(self.desc.mip_levels as u32),
-                    base_array_layer: 0,
-                    layer_count: match self.desc.image_type {
-                        ImageType::Cube | ImageType::CubeArray => 6,
-                        _ => 1,
-                    },
-                },
-                image: self.raw,
-                ..Default::default()
+            format: desc.format.unwrap_or(self.desc.format),
+            components: desc.swizzle.into(),
+            view_type: view_type(&self.desc, desc),
+            subresource_range: self.view_subresource_range(desc),
+            image: self.raw,
+            ..Default::default()
         }
     }
-    pub fn view(&self, desc: ImageViewDesc) -> ImageView {
+    pub fn view(&self, desc: ImageViewDesc) -> Result<ImageView, ImageViewDescError> {
         unsafe {
             let mut views = self.views.lock().unwrap();
-            let fb_attachment_desc = FramebufferAttachmentDesc {
-                flgas: self.desc.flags,
-                usage: self.desc.usage,
-                layer_count: desc.level_count.unwrap_or(self.desc.mip_levels as u32),
+            if let Some(view) = views.get(&desc) {
+                return Ok(view.clone());
+            }
+
+            let create_info = self.get_view_create_info(&desc);
+            validate_view_create_info(&self.desc, &create_info)?;
+            let view = ImageView {
+                fb_attachment_desc: FramebufferAttachmentDesc {
+                    flgas: self.desc.flags,
+                    usage: self.desc.usage,
+                    layer_count: create_info.subresource_range.layer_count,
+                },
+                raw: self
+                    .device
+                    .raw
+                    .create_image_view(&create_info, None)
+                    .unwrap(),
+                desc,
+                image_desc: self.desc,
             };
-            views
-                .entry(desc)
-                .or_insert_with(|| {
-                    //println!("New View Created");
-                    ImageView {
-                        fb_attachment_desc,
-                        raw: self
-                            .device
-                            .raw
-                            .create_image_view(
-                                &vk::ImageViewCreateInfo {
-                                    format: self.desc.format,
-                                    components: vk::ComponentMapping {
-                                        r: vk::ComponentSwizzle::R,
-                                        g: vk::ComponentSwizzle::G,
-                                        b: vk::ComponentSwizzle::B,
-                                        a: vk::ComponentSwizzle::A,
-                                    },
-                                    view_type: desc.view_type.unwrap_or_else(|| {
-                                        convert_image_type_to_view_type(self.desc.image_type)
-                                    }),
-                                    subresource_range: vk::ImageSubresourceRange {
-                                        aspect_mask: desc.aspect_mask,
-                                        base_mip_level: desc.base_mip_level,
-                                        level_count: desc
-                                            .level_count
-                                            .unwrap_or(self.desc.mip_levels as u32),
-                                            base_array_layer: 0,
-                                            layer_count: match self.desc.image_type {
-                                                ImageType::Cube | ImageType::CubeArray => 6,
-                                                _ => 1,
-                                            },
-                                    },
-                                    image: self.raw,
-                                    ..Default::default()
-                                },
-                                None,
-                                )
-                                    .unwrap(),
-                                    desc,
-                                    image_desc: self.desc,
-                    }
-                })
-            .clone()
+            views.insert(desc, view.clone());
+            Ok(view)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageViewDescError {
+    MipLevels { base_mip_level: u32, level_count: u32, mip_levels: u32 },
+    ArrayLayers { base_array_layer: u32, layer_count: u32, array_layers: u32 },
+    AspectMask { aspect_mask: vk::ImageAspectFlags, format: vk::Format },
+    LayerCount { view_type: vk::ImageViewType, layer_count: u32 },
+    ViewType { view_type: vk::ImageViewType, image_type: ImageType },
+}
+
+impl std::fmt::Display for ImageViewDescError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Self::MipLevels { base_mip_level, level_count, mip_levels } => write!(
+                f,
+                "ImageViewDesc mip levels {}..{} out of range for image with {} mip levels",
+                base_mip_level,
+                base_mip_level + level_count,
+                mip_levels,
+            ),
+            Self::ArrayLayers { base_array_layer, layer_count, array_layers } => write!(
+                f,
+                "ImageViewDesc array layers {}..{} out of range for image with {} array layers",
+                base_array_layer,
+                base_array_layer + layer_count,
+                array_layers,
+            ),
+            Self::AspectMask { aspect_mask, format } => write!(
+                f,
+                "ImageViewDesc aspect mask {:?} not available in format {:?}",
+                aspect_mask,
+                format,
+            ),
+            Self::LayerCount { view_type, layer_count } => write!(
+                f,
+                "ImageViewDesc view type {:?} cannot view {} array layers",
+                view_type,
+                layer_count,
+            ),
+            Self::ViewType { view_type, image_type } => write!(
+                f,
+                "ImageViewDesc view type {:?} incompatible with image type {:?}",
+                view_type,
+                image_type,
+            ),
         }
     }
 }
 
+impl std::error::Error for ImageViewDescError {}
+
+fn view_type(image_desc: &ImageDesc, desc: &ImageViewDesc) -> vk::ImageViewType {
+    desc.view_type
+        .unwrap_or_else(|| convert_image_type_to_view_type(image_desc.image_type))
+}
+
+// Without a layer count, single layer view types view one layer and cube views one cube, e.g.
+// a TYPE_2D view of a cube image is a view of the face at base_array_layer.
+fn view_subresource_range(image_desc: &ImageDesc, desc: &ImageViewDesc) -> vk::ImageSubresourceRange {
+    let layer_count = desc.layer_count.unwrap_or_else(|| match view_type(image_desc, desc) {
+        vk::ImageViewType::TYPE_1D | vk::ImageViewType::TYPE_2D | vk::ImageViewType::TYPE_3D => 1,
+        vk::ImageViewType::CUBE => 6,
+        _ => image_desc.array_layers().saturating_sub(desc.base_array_layer),
+    });
+    vk::ImageSubresourceRange {
+        aspect_mask: desc.aspect_mask,
+        base_mip_level: desc.base_mip_level,
+        level_count: desc
+            .level_count
+            .unwrap_or_else(|| image_desc.mip_levels.saturating_sub(desc.base_mip_level)),
+        base_array_layer: desc.base_array_layer,
+        layer_count,
+    }
+}
+
+fn validate_view_create_info(
+    image_desc: &ImageDesc,
+    create_info: &vk::ImageViewCreateInfo,
+) -> Result<(), ImageViewDescError> {
+    let range = &create_info.subresource_range;
+    let array_layers = image_desc.array_layers();
+
+    if range.level_count == 0 || range.base_mip_level + range.level_count > image_desc.mip_levels {
+        return Err(ImageViewDescError::MipLevels {
+            base_mip_level: range.base_mip_level,
+            level_count: range.level_count,
+            mip_levels: image_desc.mip_levels,
+        });
+    }
+    if range.layer_count == 0 || range.base_array_layer + range.layer_count > array_layers {
+        return Err(ImageViewDescError::ArrayLayers {
+            base_array_layer: range.base_array_layer,
+            layer_count: range.layer_count,
+            array_layers,
+        });
+    }
+    if !image_aspect_mask_from_format(image_desc.format).contains(range.aspect_mask) {
+        return Err(ImageViewDescError::AspectMask {
+            aspect_mask: range.aspect_mask,
+            format: image_desc.format,
+        });
+    }
+
+    let layers_valid = match create_info.view_type {
+        vk::ImageViewType::TYPE_1D | vk::ImageViewType::TYPE_2D | vk::ImageViewType::TYPE_3D => {
+            range.layer_count == 1
+        }
+        vk::ImageViewType::CUBE => range.layer_count == 6,
+        vk::ImageViewType::CUBE_ARRAY => range.layer_count.is_multiple_of(6),
+        _ => true,
+    };
+    if !layers_valid {
+        return Err(ImageViewDescError::LayerCount {
+            view_type: create_info.view_type,
+            layer_count: range.layer_count,
+        });
+    }
+
+    let type_valid = match image_desc.image_type {
+        ImageType::Tex1d | ImageType::Tex1dArray => matches!(
+            create_info.view_type,
+            vk::ImageViewType::TYPE_1D | vk::ImageViewType::TYPE_1D_ARRAY
+        ),
+        ImageType::Tex3d => create_info.view_type == vk::ImageViewType::TYPE_3D,
+        ImageType::Tex2d | ImageType::Tex2dArray => matches!(
+            create_info.view_type,
+            vk::ImageViewType::TYPE_2D | vk::ImageViewType::TYPE_2D_ARRAY
+        ),
+        ImageType::Cube | ImageType::CubeArray => matches!(
+            create_info.view_type,
+            vk::ImageViewType::TYPE_2D
+                | vk::ImageViewType::TYPE_2D_ARRAY
+                | vk::ImageViewType::CUBE
+                | vk::ImageViewType::CUBE_ARRAY
+        ),
+    };
+    if !type_valid {
+        return Err(ImageViewDescError::ViewType {
+            view_type: create_info.view_type,
+            image_type: image_desc.image_type,
+        });
+    }
+    Ok(())
+}
+
 impl Drop for Image {
     fn drop(&mut self) {
         unsafe {
             for (_, image_view) in self.views.lock().unwrap().iter() {
-                println!("destroy_image_view");
+                log::trace!("destroy_image_view");
                 self.device.destroy_image_view(image_view.raw, None);
             }
-            println!("destroy_image");
+            log::trace!("destroy_image");
             self.device.destroy_image(self.raw, None);
         }
+        if let Some(allocation) = self.allocation.take() {
+            self.device
+                .global_allocator
+                .lock()
+                .unwrap()
+                .free(allocation)
+                .expect("Image free error");
+        }
     }
 }
 
@@ -287,7 +465,7 @@ pub fn get_image_create_info(desc: &ImageDesc, initial_data: bool) -> vk::ImageC
             vk::Extent3D {
                 width: desc.extent.width,
                 height: desc.extent.height,
-                depth: desc.extent.depth as u32,
+                depth: desc.extent.depth,
             },
             1,
         ),
@@ -322,9 +500,9 @@ pub fn get_image_create_info(desc: &ImageDesc, initial_data: bool) -> vk::ImageC
         image_type,
         format: desc.format,
         extent: image_extent,
-        mip_levels: desc.mip_levels as u32,
-        array_layers: image_layers as u32,
-        samples: vk::SampleCountFlags::TYPE_1, // TODO: desc.sample_count
+        mip_levels: desc.mip_levels,
+        array_layers: image_layers,
+        samples: desc.sample_count,
         tiling: desc.tiling,
         usage: image_usage,
         sharing_mode: vk::SharingMode::EXCLUSIVE,
@@ -335,3 +513,65 @@ pub fn get_image_create_info(desc: &ImageDesc, initial_data: bool) -> vk::ImageC
         ..Default::default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(image_desc: &ImageDesc, desc: &ImageViewDesc) -> Result<vk::ImageSubresourceRange, ImageViewDescError> {
+        let create_info = vk::ImageViewCreateInfo {
+            view_type: view_type(image_desc, desc),
+            subresource_range: view_subresource_range(image_desc, desc),
+            ..Default::default()
+        };
+        validate_view_create_info(image_desc, &create_info).map(|_| create_info.subresource_range)
+    }
+
+    fn cube_desc() -> ImageDesc {
+        ImageDesc {
+            image_type: ImageType::Cube,
+            format: vk::Format::R8G8B8A8_UNORM,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn face_views_default_to_one_layer() {
+        let range = validate(&cube_desc(), &ImageViewDesc {
+            view_type: Some(vk::ImageViewType::TYPE_2D),
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_array_layer: 3,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!((range.base_array_layer, range.layer_count), (3, 1));
+
+        let range = validate(&cube_desc(), &ImageViewDesc {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(range.layer_count, 6);
+    }
+
+    #[test]
+    fn invalid_views_are_errors() {
+        let error = validate(&cube_desc(), &ImageViewDesc {
+            view_type: Some(vk::ImageViewType::TYPE_2D),
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            layer_count: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(error.unwrap_err(), ImageViewDescError::LayerCount {
+            view_type: vk::ImageViewType::TYPE_2D,
+            layer_count: 2,
+        });
+
+        let error = validate(&cube_desc(), &ImageViewDesc {
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            ..Default::default()
+        });
+        assert_eq!(error.unwrap_err(), ImageViewDescError::AspectMask {
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            format: vk::Format::R8G8B8A8_UNORM,
+        });
+    }
+}
@@ -36,9 +36,6 @@ impl SharedInstance for Arc<Instance>{
                 .expect("Physical device error");
             let (pdevice, queue_family_index) = pdevices
                 .iter()
-                .filter_map(|pdevice| {
-                    Some(pdevice)
-                })
                 .filter_map(|pdevice| {
                     self.raw
                         .get_physical_device_queue_family_properties(*pdevice)
@@ -76,9 +73,19 @@ impl SharedInstance for Arc<Instance>{
             })
             .expect("Couldn't find suitable device.");
 
+            let compute_queue_family_index = self.raw
+                .get_physical_device_queue_family_properties(pdevice)
+                .iter()
+                .position(|info| {
+                    info.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                        && !info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                })
+                .map(|index| index as u32);
+
             Arc::new(Adapter{
                 pdevice,
                 queue_family_index: queue_family_index as u32,
+                compute_queue_family_index,
                 instance: self.clone(),
             })
         }
@@ -107,7 +114,7 @@ impl Instance{
             let appinfo = vk::ApplicationInfo::builder()
                 .application_name(appname.as_c_str())
                 .application_version(0)
-                .engine_name(CStr::from_bytes_with_nul(b"hephaistos\0").unwrap())
+                .engine_name(c"hephaistos")
                 .api_version(if driver_api_version < vk::API_VERSION_1_1 {
                     vk::API_VERSION_1_0
                 } else {
@@ -123,9 +130,7 @@ impl Instance{
 
             extension_names.push(DebugUtils::name().as_ptr());
 
-            let layer_names = [CStr::from_bytes_with_nul_unchecked(
-                b"VK_LAYER_KHRONOS_validation\0",
-            )];
+            let layer_names = [c"VK_LAYER_KHRONOS_validation"];
             let layers_names_raw: Vec<*const c_char> = layer_names
                 .iter()
                 .map(|raw_name| raw_name.as_ptr())
@@ -172,12 +177,11 @@ impl Instance{
 impl Drop for Instance{
     fn drop(&mut self) {
         unsafe{
-            self.debug_utils_loader.as_ref().and_then(|d| {
+            if let Some(d) = self.debug_utils_loader.as_ref(){
                 d.destroy_debug_utils_messenger(self.debug_call_back.unwrap(), None);
-                Some(())
-            });
+            }
             self.raw.destroy_instance(None);
-            println!("debug_utils, instance");
+            log::trace!("debug_utils, instance");
         }
     }
 }
@@ -185,12 +189,11 @@ impl Drop for Instance{
 impl Drop for Surface{
     fn drop(&mut self) {
         unsafe{
-            self.swapchain.as_ref().and_then(|s|{
+            if let Some(s) = self.swapchain.as_ref(){
                 s.loader.destroy_swapchain(s.raw, None);
-                Some(())
-            });
+            }
             self.loader.destroy_surface(self.raw, None);
-            println!("swapchain, surface");
+            log::trace!("swapchain, surface");
         }
     }
 }
@@ -1,6 +1,7 @@
 
 pub mod instance;
 pub mod utils;
+pub mod format;
 pub mod descriptors;
 pub mod adapter;
 pub mod device;
@@ -13,28 +14,48 @@ pub mod barrier;
 pub mod buffer;
 pub mod commandbuffer;
 pub mod deviceframe;
+pub mod sampler;
+pub mod rendergraph;
+pub mod hazard;
+pub mod event;
+pub mod rendering;
+pub mod pipeline;
+pub mod pipelinecache;
+pub mod descriptorset;
+pub mod bindless;
+pub mod reflection;
+pub mod shader;
 
 use arrayvec::ArrayVec;
 use fxhash::FxHashMap;
 pub use self::instance::*;
 pub use self::descriptors::*;
+pub use self::format::*;
 pub use self::adapter::*;
 pub use self::device::*;
 pub use self::surface::*;
-pub use self::swapchain::*;
 pub use self::image::*;
-pub use self::framebuffer::*;
 pub use self::renderpass::*;
 pub use self::barrier::*;
 pub use self::buffer::*;
 pub use self::commandbuffer::*;
 pub use self::deviceframe::*;
+pub use self::rendergraph::*;
+pub use self::hazard::*;
+pub use self::event::*;
+pub use self::rendering::*;
+pub use self::pipeline::*;
+pub use self::bindless::*;
+pub use self::reflection::*;
+pub use self::shader::*;
 
 use std::ffi::{CStr, CString};
 
 use ash::extensions::{khr, ext};
 use ash::vk;
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::AtomicU64;
 
 use derive_more::*;
 
@@ -58,6 +79,8 @@ pub struct Surface{
 pub struct Adapter{
     pub pdevice: vk::PhysicalDevice,
     pub queue_family_index: u32,
+    // Queue family supporting compute but not graphics, if the adapter has one.
+    pub compute_queue_family_index: Option<u32>,
     pub instance: Arc<Instance>,
 }
 
@@ -71,7 +94,32 @@ pub struct SharedDevice{
     pub global_allocator: Arc<Mutex<gpu_allocator::vulkan::Allocator>>,
     pub global_queue: vk::Queue,
     pub queue_family_index: u32,
+    // Dedicated compute queue, compute work goes to global_queue without one. Buffers and images
+    // are then created with CONCURRENT sharing between both families, so no queue family
+    // ownership transfers are recorded.
+    pub compute_queue: Option<vk::Queue>,
+    pub compute_queue_family_index: Option<u32>,
     pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub features: vk::PhysicalDeviceFeatures,
+    // Set when VK_KHR_synchronization2 is enabled, barriers and submits then use the *2 commands.
+    pub synchronization2: Option<khr::Synchronization2>,
+    // Set when dynamic rendering was requested in DeviceDesc.
+    pub dynamic_rendering: Option<khr::DynamicRendering>,
+    // Set when descriptor indexing was requested in DeviceDesc.
+    pub descriptor_indexing: bool,
+    // Set when the adapter supports multiview, see RenderPassDesc::view_masks.
+    pub multiview: bool,
+    // Render passes are shared between identical descriptions, weak so unused ones are destroyed.
+    pub render_passes: Mutex<FxHashMap<RenderPassCacheKey, Weak<RenderPass>>>,
+    // Number of frames finished through BeginFrame::finish_frame.
+    pub frame_index: AtomicU64,
+    // Only set in debug builds, checks every access and barrier recorded through the library.
+    pub hazard_checker: Option<Mutex<HazardChecker>>,
+    // Used for every pipeline created through the library, see DeviceDesc::pipeline_cache_path.
+    pub pipeline_cache: vk::PipelineCache,
+    pub pipeline_cache_path: Option<PathBuf>,
+    // Layouts live as long as the device, keyed by their bindings sorted by binding index.
+    pub descriptor_set_layouts: Mutex<FxHashMap<Vec<DescriptorBindingDesc>, Arc<DescriptorSetLayout>>>,
 }
 
 #[derive(Deref, DerefMut)]
@@ -79,12 +127,18 @@ pub struct RenderDevice{
     #[deref]
     #[deref_mut]
     pub shared: Arc<SharedDevice>,
-    pub frames: [Mutex<Arc<DeviceFrame>>; 2],
+    pub frames: [Mutex<Arc<DeviceFrame>>; FRAMES_IN_FLIGHT],
     pub setup_cb: CommandBuffer,
+    pub samplers: Mutex<FxHashMap<SamplerDesc, Sampler>>,
 }
 
 pub struct DeviceFrame{
     pub main_cb: CommandBuffer,
+    // Transient render graph images with the frame they were last used in, free for reuse once
+    // the frame has been waited on.
+    pub transient_images: Mutex<Vec<(Image, u64)>>,
+    // Reset in begin_frame once the frame's fence has signaled, sets from it are only valid for one frame.
+    pub descriptor_allocator: Mutex<DescriptorAllocator>,
 }
 
 pub struct Swapchain{
@@ -114,6 +168,10 @@ pub struct Image{
     pub raw: vk::Image,
     pub desc: ImageDesc,
     pub views: Mutex<FxHashMap<ImageViewDesc, ImageView>>,
+    // Last access of every subresource, indexed by Image::subresource_index.
+    pub access: Mutex<Vec<vk_sync::AccessType>>,
+    // None when the memory isn't owned by the image, like for swapchain images.
+    pub allocation: Option<gpu_allocator::vulkan::Allocation>,
     pub device: Arc<SharedDevice>,
 }
 
@@ -133,20 +191,106 @@ pub struct ImageView{
     pub image_desc: ImageDesc,
 }
 
+#[derive(Deref, DerefMut, Clone, Copy)]
+pub struct Sampler{
+    #[deref]
+    #[deref_mut]
+    pub raw: vk::Sampler,
+    pub desc: SamplerDesc,
+}
+
 pub const MAX_COLOR_ATTACHMENTS: usize = 8;
+// Color attachments, one resolve attachment per color attachment and a depth attachment.
+pub const MAX_ATTACHMENTS: usize = MAX_COLOR_ATTACHMENTS * 2 + 1;
+
+
+pub const FRAMES_IN_FLIGHT: usize = 2;
+// Framebuffers unused for this many frames are evicted.
+pub const FRAMEBUFFER_CACHE_MAX_AGE: u64 = 64;
+// Least recently used framebuffers are evicted beyond this many entries.
+pub const FRAMEBUFFER_CACHE_CAPACITY: usize = 16;
+// Pooled render graph images unused for this many frames are destroyed.
+pub const TRANSIENT_IMAGE_MAX_AGE: u64 = 64;
 
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FramebufferCacheStats{
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+}
+
+struct FramebufferCacheEntry{
+    framebuffer: vk::Framebuffer,
+    last_used_frame: u64,
+}
+
+#[derive(Default)]
+struct FramebufferCacheState{
+    entries: FxHashMap<FramebufferCacheKey, FramebufferCacheEntry>,
+    // Evicted framebuffers that may still be used by frames in flight.
+    retired: Vec<FramebufferCacheEntry>,
+    last_eviction_frame: u64,
+    stats: FramebufferCacheStats,
+}
 
 pub struct FramebufferCache{
-    entries: Mutex<FxHashMap<FramebufferCacheKey, vk::Framebuffer>>,
-    attachment_desc: ArrayVec<vk::AttachmentDescription, MAX_COLOR_ATTACHMENTS>,
+    state: Mutex<FramebufferCacheState>,
+    attachment_desc: ArrayVec<vk::AttachmentDescription, MAX_ATTACHMENTS>,
     render_pass: vk::RenderPass,
-    color_attachment_count: usize,
+    // Multiview render passes select layers through view masks and need single layer framebuffers.
+    multiview: bool,
 }
 
-#[derive(Eq, PartialEq, Hash)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub struct FramebufferCacheKey{
     pub extent: vk::Extent2D,
-    pub attachments: ArrayVec<FramebufferAttachmentDesc, MAX_COLOR_ATTACHMENTS>,
+    pub attachments: ArrayVec<FramebufferAttachmentDesc, MAX_ATTACHMENTS>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RenderPassAttachmentKey{
+    pub flags: vk::AttachmentDescriptionFlags,
+    pub format: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    pub stencil_load_op: vk::AttachmentLoadOp,
+    pub stencil_store_op: vk::AttachmentStoreOp,
+    pub initial_layout: vk::ImageLayout,
+    pub final_layout: vk::ImageLayout,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SubpassKey{
+    pub color_attachments: Vec<u32>,
+    pub input_attachments: Vec<u32>,
+    pub resolve_attachments: Vec<u32>,
+    pub depth_attachment: Option<u32>,
+    pub depth_layout: vk::ImageLayout,
+    pub preserve_attachments: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SubpassDependencyKey{
+    pub src_subpass: u32,
+    pub dst_subpass: u32,
+    pub src_stage_mask: vk::PipelineStageFlags,
+    pub dst_stage_mask: vk::PipelineStageFlags,
+    pub src_access_mask: vk::AccessFlags,
+    pub dst_access_mask: vk::AccessFlags,
+    pub dependency_flags: vk::DependencyFlags,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RenderPassCacheKey{
+    pub attachments: Vec<RenderPassAttachmentKey>,
+    pub color_attachment_count: usize,
+    pub has_depth_attachment: bool,
+    pub subpasses: Vec<SubpassKey>,
+    pub dependencies: Vec<SubpassDependencyKey>,
+    pub view_masks: Vec<u32>,
+    pub correlation_masks: Vec<u32>,
 }
 
 #[derive(Deref, DerefMut)]
@@ -155,6 +299,82 @@ pub struct RenderPass{
     #[deref_mut]
     pub raw: vk::RenderPass,
     pub framebuffer_cache: FramebufferCache,
+    pub color_attachments: ArrayVec<vk::AttachmentDescription, MAX_COLOR_ATTACHMENTS>,
+    pub depth_attachment: Option<DepthStencilAttachmentDesc>,
+    pub resolve_attachments: ArrayVec<vk::AttachmentDescription, MAX_COLOR_ATTACHMENTS>,
+    pub subpass_count: u32,
+    pub subpass_color_attachment_counts: Vec<u32>,
+    // Sample count of the attachments of each subpass, TYPE_1 for subpasses without attachments.
+    pub subpass_samples: Vec<vk::SampleCountFlags>,
+    // Layout of the depth attachment in each subpass, None for subpasses without one.
+    pub subpass_depth_layouts: Vec<Option<vk::ImageLayout>>,
+    pub device: Arc<SharedDevice>,
+}
+
+#[derive(Deref, DerefMut)]
+pub struct Pipeline{
+    #[deref]
+    #[deref_mut]
+    pub raw: vk::Pipeline,
+    pub layout: vk::PipelineLayout,
+    pub set_layouts: Vec<vk::DescriptorSetLayout>,
+    pub reflection: PipelineReflection,
+    pub bind_point: vk::PipelineBindPoint,
+    pub device: Arc<SharedDevice>,
+}
+
+#[derive(Deref, DerefMut)]
+pub struct DescriptorSetLayout{
+    #[deref]
+    #[deref_mut]
+    pub raw: vk::DescriptorSetLayout,
+    pub bindings: Vec<DescriptorBindingDesc>,
+}
+
+// Hands out descriptor sets from a list of pools, creating a larger pool whenever all of them
+// are exhausted. Sets are not freed individually, only all at once through reset.
+pub struct DescriptorAllocator{
+    pub pools: Vec<vk::DescriptorPool>,
+    // Index of the pool sets are currently allocated from.
+    pub current_pool: usize,
+    pub device: Arc<SharedDevice>,
+}
+
+// Collects descriptor writes for one set, see DescriptorSetWriter::write.
+#[derive(Default)]
+pub struct DescriptorSetWriter{
+    pub buffer_writes: Vec<(u32, u32, vk::DescriptorType, vk::DescriptorBufferInfo)>,
+    pub image_writes: Vec<(u32, u32, vk::DescriptorType, vk::DescriptorImageInfo)>,
+}
+
+// Free indices of one array in a BindlessHeap.
+pub struct BindlessSlots{
+    pub capacity: u32,
+    // Indices below next_unused have been handed out at least once.
+    pub next_unused: u32,
+    pub free: Vec<u32>,
+    // Released indices with the frame they were released in, reused after FRAMES_IN_FLIGHT frames.
+    pub released: Vec<(u64, u32)>,
+}
+
+pub struct BindlessHeapState{
+    pub sampled_images: BindlessSlots,
+    pub storage_buffers: BindlessSlots,
+    pub samplers: BindlessSlots,
+    // Samplers are owned by the heap by index, cached ones from RenderDevice::get_sampler could be
+    // destroyed while the set still references them.
+    pub owned_samplers: FxHashMap<u32, Sampler>,
+    // Released samplers with the frame they were released in, destroyed after FRAMES_IN_FLIGHT frames.
+    pub retired_samplers: Vec<(u64, Sampler)>,
+}
+
+// One update after bind descriptor set with large arrays, resources are referenced by index.
+pub struct BindlessHeap{
+    pub set: vk::DescriptorSet,
+    pub pool: vk::DescriptorPool,
+    pub layout: Arc<DescriptorSetLayout>,
+    // Also serializes descriptor updates to the set.
+    pub state: Mutex<BindlessHeapState>,
     pub device: Arc<SharedDevice>,
 }
 
@@ -165,6 +385,14 @@ pub struct CommandBuffer{
     pub device: Arc<SharedDevice>,
 }
 
+#[derive(Deref, DerefMut)]
+pub struct Event{
+    #[deref]
+    #[deref_mut]
+    pub raw: vk::Event,
+    pub device: Arc<SharedDevice>,
+}
+
 pub struct Buffer {
     pub raw: vk::Buffer,
     pub desc: BufferDescInt,
@@ -0,0 +1,354 @@
+use super::*;
+use ash::vk;
+
+fn create_shader_module(device: &SharedDevice, code: &[u32]) -> vk::ShaderModule{
+    unsafe{
+        device.raw
+            .create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(code), None)
+            .expect("Could not create shader module")
+    }
+}
+
+// Layouts given in the description take precedence over the cached ones derived from reflection,
+// overrides replace single reflected sets and have to declare every binding the shaders use there.
+fn create_reflected_layouts(
+    device: &SharedDevice,
+    reflection: &PipelineReflection,
+    set_layouts: &[vk::DescriptorSetLayout],
+    set_layout_overrides: &[(u32, &DescriptorSetLayout)],
+    push_constant_ranges: &[vk::PushConstantRange],
+) -> (vk::PipelineLayout, Vec<vk::DescriptorSetLayout>){
+    let set_layouts = match set_layouts.is_empty(){
+        true => {
+            let mut set_bindings = reflection.set_layout_bindings();
+            let set_count = set_layout_overrides
+                .iter()
+                .map(|(set, _)| *set as usize + 1)
+                .fold(set_bindings.len(), usize::max);
+            set_bindings.resize(set_count, Vec::new());
+
+            let mut set_layouts = set_bindings
+                .iter()
+                .map(|bindings| device.get_descriptor_set_layout(&DescriptorSetLayoutDesc{bindings}).raw)
+                .collect::<Vec<_>>();
+            for (set, layout) in set_layout_overrides{
+                for reflected in set_bindings[*set as usize].iter(){
+                    assert!(
+                        layout.bindings.iter().any(|binding| {
+                            binding.binding == reflected.binding && binding.descriptor_type == reflected.descriptor_type
+                        }),
+                        "Set layout override for set {} has no {:?} binding {} used by the shaders",
+                        set, reflected.descriptor_type, reflected.binding
+                    );
+                }
+                set_layouts[*set as usize] = layout.raw;
+            }
+            set_layouts
+        }
+        false => {
+            assert!(set_layout_overrides.is_empty(), "set_layout_overrides cannot be combined with set_layouts");
+            set_layouts.to_vec()
+        }
+    };
+    let push_constant_ranges = match push_constant_ranges.is_empty(){
+        true => &reflection.push_constant_ranges[..],
+        false => push_constant_ranges,
+    };
+    let layout = create_pipeline_layout(device, &set_layouts, push_constant_ranges);
+    (layout, set_layouts)
+}
+
+fn create_pipeline_layout(
+    device: &SharedDevice,
+    set_layouts: &[vk::DescriptorSetLayout],
+    push_constant_ranges: &[vk::PushConstantRange],
+) -> vk::PipelineLayout{
+    unsafe{
+        device.raw
+            .create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder()
+                    .set_layouts(set_layouts)
+                    .push_constant_ranges(push_constant_ranges),
+                None,
+            )
+            .expect("Could not create pipeline layout")
+    }
+}
+
+impl RenderDevice{
+    pub fn create_graphics_pipeline(&self, desc: &GraphicsPipelineDesc, target: PipelineTarget) -> Pipeline{
+        let (color_attachment_count, samples, depth_layout) = match target{
+            PipelineTarget::RenderPass(render_pass) => {
+                let color_attachment_count = *render_pass.subpass_color_attachment_counts
+                    .get(desc.subpass as usize)
+                    .expect("GraphicsPipelineDesc subpass does not exist in the render pass") as usize;
+                let depth_layout = render_pass.subpass_depth_layouts[desc.subpass as usize];
+                (color_attachment_count, render_pass.samples(desc.subpass), depth_layout)
+            }
+            PipelineTarget::Rendering(formats) => {
+                assert!(self.dynamic_rendering.is_some(), "Dynamic rendering is not enabled on this device");
+                assert!(desc.subpass == 0, "GraphicsPipelineDesc subpass has to be 0 with dynamic rendering");
+                (formats.color_formats.len(), formats.samples, None)
+            }
+        };
+        assert!(
+            desc.blend.is_empty() || desc.blend.len() == color_attachment_count,
+            "GraphicsPipelineDesc needs either no blend modes or one per color attachment of the subpass"
+        );
+
+        // Shader modules are only needed until the pipeline is created.
+        let shader_modules = desc.shaders
+            .iter()
+            .map(|shader| create_shader_module(&self.shared, shader.code))
+            .collect::<Vec<_>>();
+        let shader_stage_create_infos = desc.shaders
+            .iter()
+            .zip(shader_modules.iter())
+            .map(|(shader, module)| {
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(shader.stage)
+                    .module(*module)
+                    .name(shader.entry_point)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let reflection = PipelineReflection::new(
+            &desc.shaders.iter().map(reflect_shader).collect::<Vec<_>>(),
+        );
+        let (layout, set_layouts) = create_reflected_layouts(
+            &self.shared,
+            &reflection,
+            desc.set_layouts,
+            desc.set_layout_overrides,
+            desc.push_constant_ranges,
+        );
+
+        let (vertex_bindings, vertex_attributes) = match desc.vertex_bindings.is_empty() && desc.vertex_attributes.is_empty(){
+            true if !reflection.vertex_inputs.is_empty() => {
+                let (binding, attributes) = reflection.packed_vertex_layout();
+                (vec![binding], attributes)
+            }
+            _ => {
+                // Reported instead of asserted, a mismatched input only reads undefined values.
+                if let Err(mismatches) = reflection.validate_vertex_attributes(desc.vertex_attributes){
+                    for mismatch in mismatches{
+                        log::warn!("Vertex layout does not match the vertex shader: {}", mismatch);
+                    }
+                }
+                (desc.vertex_bindings.to_vec(), desc.vertex_attributes.to_vec())
+            }
+        };
+
+        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&vertex_bindings)
+            .vertex_attribute_descriptions(&vertex_attributes);
+        let input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(desc.topology);
+        // Viewports and scissors are expected to be dynamic state.
+        let viewport_state_info = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+        let rasterization_info = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(desc.rasterization.polygon_mode)
+            .cull_mode(desc.rasterization.cull_mode)
+            .front_face(desc.rasterization.front_face)
+            .line_width(desc.rasterization.line_width);
+        let multisample_state_info = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(samples);
+        let depth_state_info = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(desc.depth_stencil.depth_test)
+            .depth_write_enable(depth_write_enable(desc.depth_stencil.depth_write, depth_layout))
+            .depth_compare_op(desc.depth_stencil.depth_compare_op)
+            .stencil_test_enable(desc.depth_stencil.stencil_test)
+            .front(desc.depth_stencil.front)
+            .back(desc.depth_stencil.back)
+            .max_depth_bounds(1.0);
+        let color_blend_attachment_states = (0..color_attachment_count)
+            .map(|attachment| desc.blend.get(attachment).copied().unwrap_or_default().into())
+            .collect::<Vec<vk::PipelineColorBlendAttachmentState>>();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(&color_blend_attachment_states);
+        let dynamic_state_info = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(desc.dynamic_states);
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stage_create_infos)
+            .vertex_input_state(&vertex_input_state_info)
+            .input_assembly_state(&input_assembly_state_info)
+            .viewport_state(&viewport_state_info)
+            .rasterization_state(&rasterization_info)
+            .multisample_state(&multisample_state_info)
+            .depth_stencil_state(&depth_state_info)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state_info)
+            .layout(layout)
+            .subpass(desc.subpass);
+        let mut rendering_info;
+        let pipeline_info = match target{
+            PipelineTarget::RenderPass(render_pass) => pipeline_info.render_pass(render_pass.raw),
+            PipelineTarget::Rendering(formats) => {
+                rendering_info = get_pipeline_rendering_create_info(formats);
+                pipeline_info.push_next(&mut rendering_info)
+            }
+        };
+
+        let pipeline = unsafe{
+            self.raw
+                .create_graphics_pipelines(self.pipeline_cache, &[pipeline_info.build()], None)
+                .expect("Could not create graphics pipeline")[0]
+        };
+
+        for module in shader_modules{
+            unsafe{
+                self.raw.destroy_shader_module(module, None);
+            }
+        }
+
+        Pipeline{
+            raw: pipeline,
+            layout,
+            set_layouts,
+            reflection,
+            bind_point: vk::PipelineBindPoint::GRAPHICS,
+            device: self.shared.clone(),
+        }
+    }
+}
+
+impl RenderDevice{
+    pub fn create_compute_pipeline(&self, desc: &ComputePipelineDesc) -> Pipeline{
+        assert!(
+            desc.shader.stage == vk::ShaderStageFlags::COMPUTE,
+            "ComputePipelineDesc needs a compute shader, got {:?}",
+            desc.shader.stage,
+        );
+
+        let module = create_shader_module(&self.shared, desc.shader.code);
+        let reflection = PipelineReflection::new(&[reflect_shader(&desc.shader)]);
+        let (layout, set_layouts) = create_reflected_layouts(
+            &self.shared,
+            &reflection,
+            desc.set_layouts,
+            desc.set_layout_overrides,
+            desc.push_constant_ranges,
+        );
+
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(vk::ShaderStageFlags::COMPUTE)
+                    .module(module)
+                    .name(desc.shader.entry_point)
+                    .build(),
+            )
+            .layout(layout);
+
+        let pipeline = unsafe{
+            self.raw
+                .create_compute_pipelines(self.pipeline_cache, &[pipeline_info.build()], None)
+                .expect("Could not create compute pipeline")[0]
+        };
+
+        unsafe{
+            self.raw.destroy_shader_module(module, None);
+        }
+
+        Pipeline{
+            raw: pipeline,
+            layout,
+            set_layouts,
+            reflection,
+            bind_point: vk::PipelineBindPoint::COMPUTE,
+            device: self.shared.clone(),
+        }
+    }
+}
+
+impl SharedDevice{
+    pub fn dispatch(&self, cb: vk::CommandBuffer, group_count: [u32; 3]){
+        unsafe{
+            self.raw.cmd_dispatch(cb, group_count[0], group_count[1], group_count[2]);
+        }
+    }
+    // The buffer holds a vk::DispatchIndirectCommand at offset.
+    pub fn dispatch_indirect(&self, cb: vk::CommandBuffer, buffer: &Buffer, offset: vk::DeviceSize){
+        unsafe{
+            self.raw.cmd_dispatch_indirect(cb, buffer.raw, offset);
+        }
+    }
+    // Dispatches enough groups of group_size (the shader's local size) to cover item_count items,
+    // shaders have to discard invocations past the end.
+    pub fn dispatch_items(&self, cb: vk::CommandBuffer, item_count: [u32; 3], group_size: [u32; 3]){
+        self.dispatch(cb, dispatch_group_count(item_count, group_size));
+    }
+}
+
+// Depth writes are disabled for subpasses that keep depth in a read only layout, so the default
+// GraphicsPipelineDesc can be used with DepthStencilAttachmentDesc::read_only.
+fn depth_write_enable(depth_write: bool, depth_layout: Option<vk::ImageLayout>) -> bool{
+    let read_only = matches!(
+        depth_layout,
+        Some(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            | Some(vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL)
+            | Some(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
+            | Some(vk::ImageLayout::READ_ONLY_OPTIMAL)
+    );
+    depth_write && !read_only
+}
+
+pub fn dispatch_group_count(item_count: [u32; 3], group_size: [u32; 3]) -> [u32; 3]{
+    assert!(group_size.iter().all(|size| *size > 0), "Group size {:?} has to be at least 1 in every dimension", group_size);
+    // Rounds up without overflowing for item counts close to u32::MAX.
+    [0, 1, 2].map(|i| item_count[i].div_ceil(group_size[i]))
+}
+
+impl Pipeline{
+    pub fn bind(&self, cb: vk::CommandBuffer){
+        unsafe{
+            self.device.cmd_bind_pipeline(cb, self.bind_point, self.raw);
+        }
+    }
+}
+
+impl Drop for Pipeline{
+    fn drop(&mut self) {
+        unsafe{
+            self.device.destroy_pipeline(self.raw, None);
+            self.device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn group_count_rounds_up(){
+        assert_eq!(dispatch_group_count([64, 65, 1], [64, 64, 1]), [1, 2, 1]);
+        assert_eq!(dispatch_group_count([0, 1, 1], [8, 8, 1]), [0, 1, 1]);
+    }
+
+    #[test]
+    fn group_count_does_not_overflow(){
+        assert_eq!(dispatch_group_count([u32::MAX, 1, 1], [64, 1, 1]), [u32::MAX / 64 + 1, 1, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn group_count_rejects_empty_groups(){
+        dispatch_group_count([1, 1, 1], [0, 1, 1]);
+    }
+
+    #[test]
+    fn read_only_depth_disables_depth_writes(){
+        let read_only = DepthStencilAttachmentDesc::read_only(vk::Format::D32_SFLOAT, vk::SampleCountFlags::TYPE_1);
+        assert!(GraphicsPipelineDesc::default().depth_stencil.depth_write);
+        assert!(!depth_write_enable(true, Some(read_only.layout)));
+        assert!(!depth_write_enable(true, Some(vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL)));
+        assert!(depth_write_enable(true, Some(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)));
+        assert!(depth_write_enable(true, None));
+        assert!(!depth_write_enable(false, Some(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)));
+    }
+}
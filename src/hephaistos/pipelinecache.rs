@@ -0,0 +1,83 @@
+use super::*;
+use ash::vk;
+use std::path::Path;
+
+// Size of the VK_PIPELINE_CACHE_HEADER_VERSION_ONE header at the start of the cache data.
+const PIPELINE_CACHE_HEADER_SIZE: usize = 16 + 16;
+
+fn read_u32(data: &[u8], offset: usize) -> u32{
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+// Data written by another driver or GPU is rejected here instead of trusting the driver
+// to ignore it.
+fn is_pipeline_cache_compatible(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool{
+    if data.len() < PIPELINE_CACHE_HEADER_SIZE{
+        return false;
+    }
+    let header_size = read_u32(data, 0) as usize;
+    let header_version = read_u32(data, 4) as i32;
+    let vendor_id = read_u32(data, 8);
+    let device_id = read_u32(data, 12);
+    let uuid = &data[16..32];
+
+    header_size >= PIPELINE_CACHE_HEADER_SIZE
+        && header_size <= data.len()
+        && header_version == vk::PipelineCacheHeaderVersion::ONE.as_raw()
+        && vendor_id == properties.vendor_id
+        && device_id == properties.device_id
+        && uuid == properties.pipeline_cache_uuid.as_slice()
+}
+
+impl Adapter{
+    // Starts out empty if the file is missing, unreadable or was written for a different device.
+    pub(crate) fn create_pipeline_cache(&self, device: &ash::Device, path: Option<&Path>) -> vk::PipelineCache{
+        let properties = unsafe{self.instance.raw.get_physical_device_properties(self.pdevice)};
+        let initial_data = match path.map(std::fs::read){
+            Some(Ok(data)) if is_pipeline_cache_compatible(&data, &properties) => data,
+            Some(Ok(_)) => {
+                log::warn!("Discarding pipeline cache {:?}, it was created for a different device or driver", path.unwrap());
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+
+        unsafe{
+            device
+                .create_pipeline_cache(
+                    &vk::PipelineCacheCreateInfo::builder().initial_data(&initial_data),
+                    None,
+                )
+                .expect("Could not create pipeline cache")
+        }
+    }
+}
+
+impl SharedDevice{
+    // Called when the device is dropped, can also be called earlier so a crash does not lose the cache.
+    // The data is written next to the target and renamed over it, so an interrupted write never
+    // leaves a truncated cache behind.
+    pub fn save_pipeline_cache(&self){
+        let path = match &self.pipeline_cache_path{
+            Some(path) => path,
+            None => return,
+        };
+        let data = unsafe{
+            self.raw
+                .get_pipeline_cache_data(self.pipeline_cache)
+                .expect("Could not get pipeline cache data")
+        };
+        if data.len() < PIPELINE_CACHE_HEADER_SIZE{
+            log::warn!("Not saving pipeline cache {:?}, the driver returned only {} bytes", path, data.len());
+            return;
+        }
+
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let result = std::fs::write(&temp_path, &data).and_then(|_| std::fs::rename(&temp_path, path));
+        if let Err(err) = result{
+            log::error!("Could not write pipeline cache {:?}: {}", path, err);
+            let _ = std::fs::remove_file(&temp_path);
+        }
+    }
+}
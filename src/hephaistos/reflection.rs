@@ -0,0 +1,408 @@
+use super::*;
+use ash::vk;
+use rspirv_reflect::rspirv::dr::{Instruction, Module, Operand};
+use rspirv_reflect::rspirv::spirv;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReflectedBinding{
+    pub name: String,
+    pub descriptor_type: vk::DescriptorType,
+    // None for unbounded (runtime sized) arrays.
+    pub count: Option<u32>,
+    pub stage_flags: vk::ShaderStageFlags,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReflectedVertexInput{
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VertexAttributeMismatch{
+    Missing{location: u32, shader_format: vk::Format},
+    NumericType{location: u32, shader_format: vk::Format, attribute_format: vk::Format},
+    UnsupportedFormat{location: u32, format: vk::Format},
+}
+
+impl std::fmt::Display for VertexAttributeMismatch{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self{
+            Self::Missing{location, shader_format} => {
+                write!(f, "location {} ({:?}) has no vertex attribute", location, shader_format)
+            }
+            Self::NumericType{location, shader_format, attribute_format} => write!(
+                f,
+                "location {} is read as {:?} but the vertex attribute is {:?}",
+                location, shader_format, attribute_format,
+            ),
+            Self::UnsupportedFormat{location, format} => {
+                write!(f, "location {} uses {:?}, which is not a vertex buffer format", location, format)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection{
+    pub stage: vk::ShaderStageFlags,
+    // Bindings by set and binding index.
+    pub descriptor_sets: BTreeMap<u32, BTreeMap<u32, ReflectedBinding>>,
+    pub push_constant_range: Option<vk::PushConstantRange>,
+    // Only filled for vertex shaders, sorted by location.
+    pub vertex_inputs: Vec<ReflectedVertexInput>,
+    pub compute_group_size: Option<[u32; 3]>,
+}
+
+// Merged reflection of all stages of a pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineReflection{
+    pub descriptor_sets: BTreeMap<u32, BTreeMap<u32, ReflectedBinding>>,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+    pub vertex_inputs: Vec<ReflectedVertexInput>,
+    pub compute_group_size: Option<[u32; 3]>,
+}
+
+pub fn reflect_shader(shader: &ShaderStageDesc) -> ShaderReflection{
+    let reflection = rspirv_reflect::Reflection::new_from_spirv(bytemuck::cast_slice(shader.code))
+        .expect("Could not parse SPIR-V for reflection");
+
+    let descriptor_sets = reflection.get_descriptor_sets()
+        .expect("Could not reflect descriptor sets")
+        .into_iter()
+        .map(|(set, bindings)| {
+            let bindings = bindings
+                .into_iter()
+                .map(|(binding, info)| {
+                    (binding, ReflectedBinding{
+                        name: info.name,
+                        descriptor_type: vk::DescriptorType::from_raw(info.ty.0 as i32),
+                        count: match info.binding_count{
+                            rspirv_reflect::BindingCount::One => Some(1),
+                            rspirv_reflect::BindingCount::StaticSized(count) => Some(count as u32),
+                            rspirv_reflect::BindingCount::Unbounded => None,
+                        },
+                        stage_flags: shader.stage,
+                    })
+                })
+                .collect();
+            (set, bindings)
+        })
+        .collect();
+
+    let push_constant_range = reflection.get_push_constant_range()
+        .expect("Could not reflect push constants")
+        .map(|info| vk::PushConstantRange{
+            stage_flags: shader.stage,
+            offset: info.offset,
+            size: info.size,
+        });
+
+    let vertex_inputs = match shader.stage{
+        vk::ShaderStageFlags::VERTEX => reflect_vertex_inputs(&reflection.0, shader),
+        _ => Vec::new(),
+    };
+
+    ShaderReflection{
+        stage: shader.stage,
+        descriptor_sets,
+        push_constant_range,
+        vertex_inputs,
+        compute_group_size: reflection.get_compute_group_size().map(|(x, y, z)| [x, y, z]),
+    }
+}
+
+fn find_type(module: &Module, id: spirv::Word) -> Option<&Instruction>{
+    module.types_global_values.iter().find(|inst| inst.result_id == Some(id))
+}
+
+fn id_operand(inst: &Instruction, index: usize) -> Option<spirv::Word>{
+    match inst.operands.get(index){
+        Some(Operand::IdRef(id)) => Some(*id),
+        _ => None,
+    }
+}
+
+fn literal_operand(inst: &Instruction, index: usize) -> Option<u32>{
+    match inst.operands.get(index){
+        Some(Operand::LiteralBit32(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+// Format of a scalar or vector type, one per location it occupies (matrices use one per column).
+fn vertex_input_formats(module: &Module, type_id: spirv::Word) -> Vec<vk::Format>{
+    let inst = match find_type(module, type_id){
+        Some(inst) => inst,
+        None => return Vec::new(),
+    };
+    match inst.class.opcode{
+        spirv::Op::TypeMatrix => {
+            let column_type = id_operand(inst, 0).unwrap_or_default();
+            let columns = literal_operand(inst, 1).unwrap_or(0);
+            (0..columns)
+                .flat_map(|_| vertex_input_formats(module, column_type))
+                .collect()
+        }
+        spirv::Op::TypeVector => {
+            let component_count = literal_operand(inst, 1).unwrap_or(1);
+            id_operand(inst, 0)
+                .and_then(|component_type| find_type(module, component_type))
+                .and_then(|component| scalar_format(component, component_count))
+                .into_iter()
+                .collect()
+        }
+        _ => scalar_format(inst, 1).into_iter().collect(),
+    }
+}
+
+fn scalar_format(inst: &Instruction, component_count: u32) -> Option<vk::Format>{
+    use vk::Format as F;
+    let width = literal_operand(inst, 0)?;
+    let formats = match inst.class.opcode{
+        spirv::Op::TypeFloat => match width{
+            16 => [F::R16_SFLOAT, F::R16G16_SFLOAT, F::R16G16B16_SFLOAT, F::R16G16B16A16_SFLOAT],
+            32 => [F::R32_SFLOAT, F::R32G32_SFLOAT, F::R32G32B32_SFLOAT, F::R32G32B32A32_SFLOAT],
+            64 => [F::R64_SFLOAT, F::R64G64_SFLOAT, F::R64G64B64_SFLOAT, F::R64G64B64A64_SFLOAT],
+            _ => return None,
+        },
+        spirv::Op::TypeInt => match (width, literal_operand(inst, 1)? != 0){
+            (32, true) => [F::R32_SINT, F::R32G32_SINT, F::R32G32B32_SINT, F::R32G32B32A32_SINT],
+            (32, false) => [F::R32_UINT, F::R32G32_UINT, F::R32G32B32_UINT, F::R32G32B32A32_UINT],
+            (16, true) => [F::R16_SINT, F::R16G16_SINT, F::R16G16B16_SINT, F::R16G16B16A16_SINT],
+            (16, false) => [F::R16_UINT, F::R16G16_UINT, F::R16G16B16_UINT, F::R16G16B16A16_UINT],
+            _ => return None,
+        },
+        _ => return None,
+    };
+    formats.get(component_count as usize - 1).copied()
+}
+
+fn reflect_vertex_inputs(module: &Module, shader: &ShaderStageDesc) -> Vec<ReflectedVertexInput>{
+    // Interface variables of the entry point, after execution model, function and name.
+    let entry_point_name = shader.entry_point.to_string_lossy();
+    let interface = module.entry_points
+        .iter()
+        .find(|inst| matches!(inst.operands.get(2), Some(Operand::LiteralString(name)) if *name == entry_point_name))
+        .map(|inst| (3..inst.operands.len()).filter_map(|i| id_operand(inst, i)).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let location = |id: spirv::Word| {
+        module.annotations.iter().find_map(|inst| {
+            let decorates_location = inst.class.opcode == spirv::Op::Decorate
+                && id_operand(inst, 0) == Some(id)
+                && inst.operands.get(1) == Some(&Operand::Decoration(spirv::Decoration::Location));
+            match decorates_location{
+                true => literal_operand(inst, 2),
+                false => None,
+            }
+        })
+    };
+
+    let mut inputs = module.types_global_values
+        .iter()
+        .filter(|inst| {
+            inst.class.opcode == spirv::Op::Variable
+                && inst.operands.first() == Some(&Operand::StorageClass(spirv::StorageClass::Input))
+                && inst.result_id.is_some_and(|id| interface.contains(&id))
+        })
+        // Built-ins such as gl_VertexIndex have no location.
+        .filter_map(|inst| {
+            let location = location(inst.result_id?)?;
+            let pointee = find_type(module, inst.result_type?).and_then(|pointer| id_operand(pointer, 1))?;
+            Some((location, vertex_input_formats(module, pointee)))
+        })
+        .flat_map(|(location, formats)| {
+            formats
+                .into_iter()
+                .enumerate()
+                .map(move |(i, format)| ReflectedVertexInput{
+                    location: location + i as u32,
+                    format,
+                })
+        })
+        .collect::<Vec<_>>();
+    inputs.sort_by_key(|input| input.location);
+    inputs
+}
+
+impl PipelineReflection{
+    pub fn new(shaders: &[ShaderReflection]) -> Self{
+        let mut reflection = PipelineReflection::default();
+        for shader in shaders{
+            for (set, bindings) in shader.descriptor_sets.iter(){
+                let merged = reflection.descriptor_sets.entry(*set).or_default();
+                for (binding, info) in bindings.iter(){
+                    match merged.get_mut(binding){
+                        Some(existing) => {
+                            assert!(
+                                existing.descriptor_type == info.descriptor_type,
+                                "Shader stages disagree on the type of set {} binding {} ({:?} and {:?})",
+                                set, binding, existing.descriptor_type, info.descriptor_type,
+                            );
+                            existing.stage_flags |= info.stage_flags;
+                        }
+                        None => {
+                            merged.insert(*binding, info.clone());
+                        }
+                    }
+                }
+            }
+            reflection.push_constant_ranges.extend(shader.push_constant_range);
+            if shader.stage == vk::ShaderStageFlags::VERTEX{
+                reflection.vertex_inputs = shader.vertex_inputs.clone();
+            }
+            reflection.compute_group_size = reflection.compute_group_size.or(shader.compute_group_size);
+        }
+        reflection
+    }
+
+    // Bindings for every set index up to the highest one used, gaps get empty sets.
+    pub fn set_layout_bindings(&self) -> Vec<Vec<DescriptorBindingDesc>>{
+        let set_count = self.descriptor_sets.keys().next_back().map_or(0, |set| set + 1);
+        (0..set_count)
+            .map(|set| {
+                self.descriptor_sets
+                    .get(&set)
+                    .into_iter()
+                    .flat_map(|bindings| bindings.iter())
+                    .map(|(binding, info)| DescriptorBindingDesc{
+                        binding: *binding,
+                        descriptor_type: info.descriptor_type,
+                        count: info.count.unwrap_or(1),
+                        stage_flags: info.stage_flags,
+                        flags: vk::DescriptorBindingFlags::empty(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // A single tightly packed vertex buffer binding in location order.
+    pub fn packed_vertex_layout(&self) -> (vk::VertexInputBindingDescription, Vec<vk::VertexInputAttributeDescription>){
+        let mut offset = 0;
+        let attributes = self.vertex_inputs
+            .iter()
+            .map(|input| {
+                let attribute = vk::VertexInputAttributeDescription{
+                    location: input.location,
+                    binding: 0,
+                    format: input.format,
+                    offset,
+                };
+                offset += format_size(input.format).expect("Reflected vertex input formats are vertex buffer formats");
+                attribute
+            })
+            .collect();
+        let binding = vk::VertexInputBindingDescription{
+            binding: 0,
+            stride: offset,
+            input_rate: vk::VertexInputRate::VERTEX,
+        };
+        (binding, attributes)
+    }
+
+    // Lists every shader input the attributes do not provide or provide with the wrong type,
+    // and every attribute whose format cannot be read from a vertex buffer.
+    pub fn validate_vertex_attributes(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Result<(), Vec<VertexAttributeMismatch>>{
+        let unsupported = attributes
+            .iter()
+            .filter(|attribute| format_size(attribute.format).is_none())
+            .map(|attribute| VertexAttributeMismatch::UnsupportedFormat{
+                location: attribute.location,
+                format: attribute.format,
+            });
+        let mismatches = self.vertex_inputs
+            .iter()
+            .filter_map(|input| {
+                match attributes.iter().find(|attribute| attribute.location == input.location){
+                    None => Some(VertexAttributeMismatch::Missing{
+                        location: input.location,
+                        shader_format: input.format,
+                    }),
+                    Some(attribute) if format_numeric_type(attribute.format) != format_numeric_type(input.format) => {
+                        Some(VertexAttributeMismatch::NumericType{
+                            location: input.location,
+                            shader_format: input.format,
+                            attribute_format: attribute.format,
+                        })
+                    }
+                    Some(_) => None,
+                }
+            })
+            .chain(unsupported)
+            .collect::<Vec<_>>();
+        if mismatches.is_empty(){
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn attribute(location: u32, format: vk::Format) -> vk::VertexInputAttributeDescription{
+        vk::VertexInputAttributeDescription{
+            location,
+            binding: 0,
+            format,
+            offset: 0,
+        }
+    }
+
+    fn reflection(inputs: &[(u32, vk::Format)]) -> PipelineReflection{
+        PipelineReflection{
+            vertex_inputs: inputs
+                .iter()
+                .map(|&(location, format)| ReflectedVertexInput{location, format})
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normalized_and_packed_attributes_match_float_inputs(){
+        let reflection = reflection(&[(0, vk::Format::R32G32B32A32_SFLOAT), (1, vk::Format::R32G32B32A32_SFLOAT)]);
+        let attributes = [
+            attribute(0, vk::Format::R8G8B8A8_UNORM),
+            attribute(1, vk::Format::A2B10G10R10_UNORM_PACK32),
+        ];
+        assert_eq!(reflection.validate_vertex_attributes(&attributes), Ok(()));
+    }
+
+    #[test]
+    fn mismatches_are_reported(){
+        let reflection = reflection(&[(0, vk::Format::R32G32_SFLOAT), (1, vk::Format::R32_UINT)]);
+        let attributes = [
+            attribute(1, vk::Format::R32_SFLOAT),
+            attribute(2, vk::Format::BC1_RGB_UNORM_BLOCK),
+        ];
+        assert_eq!(
+            reflection.validate_vertex_attributes(&attributes),
+            Err(vec![
+                VertexAttributeMismatch::Missing{location: 0, shader_format: vk::Format::R32G32_SFLOAT},
+                VertexAttributeMismatch::NumericType{
+                    location: 1,
+                    shader_format: vk::Format::R32_UINT,
+                    attribute_format: vk::Format::R32_SFLOAT,
+                },
+                VertexAttributeMismatch::UnsupportedFormat{location: 2, format: vk::Format::BC1_RGB_UNORM_BLOCK},
+            ])
+        );
+    }
+
+    #[test]
+    fn packed_layout_offsets(){
+        let reflection = reflection(&[(0, vk::Format::R32G32B32_SFLOAT), (1, vk::Format::R16G16_SFLOAT), (2, vk::Format::R32_UINT)]);
+        let (binding, attributes) = reflection.packed_vertex_layout();
+        assert_eq!(binding.stride, 20);
+        assert_eq!(attributes.iter().map(|attribute| attribute.offset).collect::<Vec<_>>(), [0, 12, 16]);
+    }
+}
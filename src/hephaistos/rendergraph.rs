@@ -0,0 +1,362 @@
+use super::*;
+use ash::vk;
+use std::sync::atomic::Ordering;
+use vk_sync::AccessType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphImage(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphBuffer(usize);
+
+enum GraphResource<'a>{
+    TransientImage(ImageDesc),
+    // The access the image starts out in and whether its contents are discarded by the first barrier.
+    ImportedImage(&'a Image, AccessType, bool),
+    ImportedBuffer(&'a Buffer, AccessType),
+}
+
+impl<'a> GraphResource<'a>{
+    fn is_imported(&self) -> bool{
+        !matches!(self, GraphResource::TransientImage(_))
+    }
+    fn discards_contents(&self) -> bool{
+        match self{
+            GraphResource::TransientImage(_) => true,
+            GraphResource::ImportedImage(_, _, discard) => *discard,
+            GraphResource::ImportedBuffer(..) => false,
+        }
+    }
+}
+
+type RenderCallback<'a> = Box<dyn FnOnce(&mut PassContext) + 'a>;
+
+struct GraphPass<'a>{
+    name: String,
+    accesses: Vec<(usize, AccessType)>,
+    render: Option<RenderCallback<'a>>,
+}
+
+// Passes are recorded in the order they were added, they are not reordered by their
+// dependencies. Barriers between them are derived from the declared accesses.
+pub struct RenderGraph<'a>{
+    resources: Vec<GraphResource<'a>>,
+    passes: Vec<GraphPass<'a>>,
+    // Accesses imported resources are transitioned to after the last pass.
+    exports: Vec<(usize, AccessType)>,
+}
+
+pub struct PassBuilder<'g, 'a>{
+    graph: &'g mut RenderGraph<'a>,
+    pass: usize,
+}
+
+pub struct PassContext<'r>{
+    pub device: &'r RenderDevice,
+    pub cb: vk::CommandBuffer,
+    images: &'r [Option<&'r Image>],
+    buffers: &'r [Option<&'r Buffer>],
+}
+
+impl<'r> PassContext<'r>{
+    pub fn image(&self, handle: GraphImage) -> &'r Image{
+        self.images[handle.0].expect("GraphImage does not belong to this graph")
+    }
+    pub fn buffer(&self, handle: GraphBuffer) -> &'r Buffer{
+        self.buffers[handle.0].expect("GraphBuffer does not belong to this graph")
+    }
+}
+
+impl<'g, 'a> PassBuilder<'g, 'a>{
+    pub fn read_image(&mut self, image: GraphImage, access: AccessType) -> &mut Self{
+        assert!(!is_write_access(access), "{:?} is not a read access", access);
+        self.graph.passes[self.pass].accesses.push((image.0, access));
+        self
+    }
+    pub fn write_image(&mut self, image: GraphImage, access: AccessType) -> &mut Self{
+        assert!(is_write_access(access), "{:?} is not a write access", access);
+        self.graph.passes[self.pass].accesses.push((image.0, access));
+        self
+    }
+    pub fn read_buffer(&mut self, buffer: GraphBuffer, access: AccessType) -> &mut Self{
+        assert!(!is_write_access(access), "{:?} is not a read access", access);
+        self.graph.passes[self.pass].accesses.push((buffer.0, access));
+        self
+    }
+    pub fn write_buffer(&mut self, buffer: GraphBuffer, access: AccessType) -> &mut Self{
+        assert!(is_write_access(access), "{:?} is not a write access", access);
+        self.graph.passes[self.pass].accesses.push((buffer.0, access));
+        self
+    }
+    pub fn render(self, render: impl FnOnce(&mut PassContext) + 'a){
+        self.graph.passes[self.pass].render = Some(Box::new(render));
+    }
+}
+
+impl<'a> Default for RenderGraph<'a>{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> RenderGraph<'a>{
+    pub fn new() -> Self{
+        Self{
+            resources: Vec::new(),
+            passes: Vec::new(),
+            exports: Vec::new(),
+        }
+    }
+
+    // Transient images are taken from the frame's pool at execution time.
+    pub fn create_image(&mut self, desc: ImageDesc) -> GraphImage{
+        self.resources.push(GraphResource::TransientImage(desc));
+        GraphImage(self.resources.len() - 1)
+    }
+
+    // Imported images start out in the access they were last left in by Image::transition.
+    // The graph tracks whole images, so all subresources have to be in the same access.
+    pub fn import_image(&mut self, image: &'a Image) -> GraphImage{
+        let access = image.uniform_access()
+            .expect("Images imported into a RenderGraph need the same access in every subresource");
+        self.resources.push(GraphResource::ImportedImage(image, access, false));
+        GraphImage(self.resources.len() - 1)
+    }
+
+    // The acquire semaphore is waited on at COLOR_ATTACHMENT_OUTPUT, so the first barrier has to
+    // start at that stage for the layout transition to wait for the presentation engine. The
+    // previous contents are discarded.
+    pub fn import_swapchain_image(&mut self, image: &'a Image) -> GraphImage{
+        self.resources.push(GraphResource::ImportedImage(image, AccessType::ColorAttachmentWrite, true));
+        GraphImage(self.resources.len() - 1)
+    }
+
+    pub fn import_buffer(&mut self, buffer: &'a Buffer, access: AccessType) -> GraphBuffer{
+        self.resources.push(GraphResource::ImportedBuffer(buffer, access));
+        GraphBuffer(self.resources.len() - 1)
+    }
+
+    // Transitions the image after the last pass, e.g. to AccessType::Present for swapchain images.
+    pub fn export_image(&mut self, image: GraphImage, access: AccessType){
+        assert!(
+            self.resources[image.0].is_imported(),
+            "Only imported images can be exported"
+        );
+        self.exports.push((image.0, access));
+    }
+
+    pub fn add_pass<'g>(&'g mut self, name: &str) -> PassBuilder<'g, 'a>{
+        self.passes.push(GraphPass{
+            name: name.to_string(),
+            accesses: Vec::new(),
+            render: None,
+        });
+        PassBuilder{
+            pass: self.passes.len() - 1,
+            graph: self,
+        }
+    }
+
+    // Passes can only use resources created before them, so declaration order is already a
+    // valid order. Passes whose writes never reach an imported resource are culled.
+    fn schedule(&self) -> Vec<usize>{
+        let mut needed = self.resources
+            .iter()
+            .map(|resource| resource.is_imported())
+            .collect::<Vec<_>>();
+        let mut scheduled = Vec::new();
+
+        for (index, pass) in self.passes.iter().enumerate().rev(){
+            let contributes = pass.accesses
+                .iter()
+                .any(|(resource, access)| is_write_access(*access) && needed[*resource]);
+
+            if contributes{
+                for (resource, _) in pass.accesses.iter(){
+                    needed[*resource] = true;
+                }
+                scheduled.push(index);
+            }
+        }
+
+        scheduled.reverse();
+        scheduled
+    }
+
+    // Records all passes in declaration order into the frame's main command buffer; it still has
+    // to be submitted.
+    pub fn execute(mut self, device: &RenderDevice, frame: &DeviceFrame){
+        let cb = frame.main_cb.raw;
+        let schedule = self.schedule();
+        let frame_index = device.frame_index.load(Ordering::Relaxed);
+
+        let mut transient_pool = frame.transient_images.lock().unwrap();
+        // Images left over from e.g. a previous window size are destroyed, freeing their memory,
+        // once they go unused.
+        transient_pool.retain(|(_, last_used_frame)| frame_index - last_used_frame <= TRANSIENT_IMAGE_MAX_AGE);
+        let transient_images = self.resources
+            .iter()
+            .map(|resource| match resource{
+                GraphResource::TransientImage(desc) => Some(
+                    match transient_pool.iter().position(|(image, _)| image.desc == *desc){
+                        Some(index) => transient_pool.swap_remove(index).0,
+                        None => device.create_image(desc, Vec::new()),
+                    }
+                ),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let images = self.resources
+            .iter()
+            .zip(transient_images.iter())
+            .map(|(resource, transient)| match resource{
+                GraphResource::TransientImage(_) => transient.as_ref(),
+                GraphResource::ImportedImage(image, ..) => Some(*image),
+                GraphResource::ImportedBuffer(..) => None,
+            })
+            .collect::<Vec<_>>();
+        let buffers = self.resources
+            .iter()
+            .map(|resource| match resource{
+                GraphResource::ImportedBuffer(buffer, _) => Some(*buffer),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = self.resources
+            .iter()
+            .map(|resource| match resource{
+                GraphResource::TransientImage(_) => vec![AccessType::Nothing],
+                GraphResource::ImportedImage(_, access, _) => vec![*access],
+                GraphResource::ImportedBuffer(_, access) => vec![*access],
+            })
+            .collect::<Vec<_>>();
+        let mut discard = self.resources
+            .iter()
+            .map(GraphResource::discards_contents)
+            .collect::<Vec<_>>();
+
+        unsafe{
+            device.raw.reset_command_buffer(cb, vk::CommandBufferResetFlags::RELEASE_RESOURCES)
+                .expect("Could not reset command buffer");
+            device.raw.begin_command_buffer(
+                cb,
+                &vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            ).expect("Could not begin command buffer");
+        }
+        if let Some(hazard_checker) = &device.hazard_checker{
+            hazard_checker.lock().unwrap().begin_command_buffer(cb);
+        }
+
+        for pass_index in schedule{
+            let pass = &mut self.passes[pass_index];
+
+            let mut next_state = Vec::<(usize, Vec<AccessType>)>::new();
+            for (resource, access) in pass.accesses.iter(){
+                match next_state.iter_mut().find(|(r, _)| r == resource){
+                    Some((_, accesses)) => accesses.push(*access),
+                    None => next_state.push((*resource, vec![*access])),
+                }
+            }
+
+            // Repeated reads with the same accesses need no synchronization.
+            let mut transitions = Vec::new();
+            for (resource, next_accesses) in next_state{
+                let needs_barrier = state[resource] != next_accesses
+                    || next_accesses.iter().any(|access| is_write_access(*access));
+                if needs_barrier{
+                    let prev_accesses = std::mem::replace(&mut state[resource], next_accesses.clone());
+                    let discard = std::mem::replace(&mut discard[resource], false);
+                    transitions.push((resource, prev_accesses, next_accesses, discard));
+                }
+            }
+
+            record_transitions(device, cb, &images, &buffers, &transitions);
+
+            if let Some(hazard_checker) = &device.hazard_checker{
+                let mut hazard_checker = hazard_checker.lock().unwrap();
+                hazard_checker.set_scope(cb, Some(&pass.name));
+                let mut resources = pass.accesses.iter().map(|(resource, _)| *resource).collect::<Vec<_>>();
+                resources.sort_unstable();
+                resources.dedup();
+                for resource in resources.iter(){
+                    let resource_id = match (images[*resource], buffers[*resource]){
+                        (Some(image), _) => ResourceId::Image(image.raw),
+                        (_, Some(buffer)) => ResourceId::Buffer(buffer.raw),
+                        _ => continue,
+                    };
+                    hazard_checker.record_accesses(cb, resource_id, &state[*resource]);
+                }
+                hazard_checker.set_scope(cb, None);
+            }
+
+            if let Some(render) = pass.render.take(){
+                render(&mut PassContext{
+                    device,
+                    cb,
+                    images: &images,
+                    buffers: &buffers,
+                });
+            } else {
+                log::warn!("RenderGraph pass \"{}\" has no render callback", pass.name);
+            }
+        }
+
+        let transitions = self.exports
+            .iter()
+            .map(|(resource, access)| {
+                let prev_accesses = std::mem::replace(&mut state[*resource], vec![*access]);
+                let discard = std::mem::replace(&mut discard[*resource], false);
+                (*resource, prev_accesses, vec![*access], discard)
+            })
+            .collect::<Vec<_>>();
+        record_transitions(device, cb, &images, &buffers, &transitions);
+
+        unsafe{
+            device.raw.end_command_buffer(cb).expect("Could not end command buffer");
+        }
+
+        for (resource, accesses) in self.resources.iter().zip(state.iter()){
+            if let GraphResource::ImportedImage(image, ..) = resource{
+                image.set_access(image.subresource_range(), accesses[accesses.len() - 1]);
+            }
+        }
+
+        // The images stay in use until the frame's fence signals, which begin_frame waits for.
+        transient_pool.extend(transient_images.into_iter().flatten().map(|image| (image, frame_index)));
+    }
+}
+
+fn record_transitions(
+    device: &RenderDevice,
+    cb: vk::CommandBuffer,
+    images: &[Option<&Image>],
+    buffers: &[Option<&Buffer>],
+    transitions: &[(usize, Vec<AccessType>, Vec<AccessType>, bool)],
+){
+    let image_barriers = transitions
+        .iter()
+        .filter_map(|(resource, prev_accesses, next_accesses, discard)| {
+            images[*resource].map(|image| {
+                ImageBarrier::new(
+                    image.raw,
+                    prev_accesses,
+                    next_accesses,
+                    image_aspect_mask_from_format(image.desc.format),
+                ).with_discard(*discard)
+            })
+        })
+        .collect::<Vec<_>>();
+    let buffer_barriers = transitions
+        .iter()
+        .filter_map(|(resource, prev_accesses, next_accesses, _)| {
+            buffers[*resource].map(|buffer| {
+                BufferBarrier::new(buffer.raw, prev_accesses, next_accesses)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    record_barriers(&device.shared, cb, None, &buffer_barriers, &image_barriers);
+}
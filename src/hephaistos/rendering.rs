@@ -0,0 +1,104 @@
+use super::*;
+use ash::vk;
+
+// Dynamic rendering, an alternative to RenderPass that needs neither render pass nor framebuffer
+// objects. Only available if the device was requested with DeviceDesc::dynamic_rendering.
+
+fn get_rendering_attachment_info(
+    attachment: &RenderingAttachmentDesc,
+    layout: vk::ImageLayout,
+    resolve_mode: vk::ResolveModeFlags,
+) -> vk::RenderingAttachmentInfo {
+    let mut info = vk::RenderingAttachmentInfo::builder()
+        .image_view(attachment.view.raw)
+        .image_layout(layout)
+        .load_op(attachment.load_op)
+        .store_op(attachment.store_op)
+        .clear_value(attachment.clear_value);
+
+    if let Some(resolve_view) = attachment.resolve_view {
+        info = info
+            .resolve_mode(resolve_mode)
+            .resolve_image_view(resolve_view.raw)
+            .resolve_image_layout(layout);
+    }
+    info.build()
+}
+
+impl SharedDevice {
+    pub fn begin_rendering(&self, desc: &RenderingDesc, cb: vk::CommandBuffer) {
+        let dynamic_rendering = self.dynamic_rendering
+            .as_ref()
+            .expect("Dynamic rendering is not enabled on this device");
+
+        let color_attachments = desc.color_attachments
+            .iter()
+            .map(|attachment| {
+                get_rendering_attachment_info(
+                    attachment,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    vk::ResolveModeFlags::AVERAGE,
+                )
+            })
+            .collect::<ArrayVec<_, MAX_COLOR_ATTACHMENTS>>();
+
+        let depth_attachment = desc.depth_attachment.as_ref().map(|attachment| {
+            get_rendering_attachment_info(
+                attachment,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                vk::ResolveModeFlags::SAMPLE_ZERO,
+            )
+        });
+        let has_stencil = desc.depth_attachment
+            .as_ref()
+            .map(|attachment| {
+                image_aspect_mask_from_format(attachment.view.image_desc.format)
+                    .contains(vk::ImageAspectFlags::STENCIL)
+            })
+            .unwrap_or(false);
+
+        let mut rendering_info = vk::RenderingInfo::builder()
+            .render_area(desc.area)
+            .layer_count(desc.layer_count)
+            .view_mask(desc.view_mask)
+            .color_attachments(&color_attachments);
+
+        if let Some(depth_attachment) = &depth_attachment {
+            rendering_info = rendering_info.depth_attachment(depth_attachment);
+            if has_stencil {
+                rendering_info = rendering_info.stencil_attachment(depth_attachment);
+            }
+        }
+
+        unsafe {
+            dynamic_rendering.cmd_begin_rendering(cb, &rendering_info);
+        }
+    }
+
+    pub fn end_rendering(&self, cb: vk::CommandBuffer) {
+        let dynamic_rendering = self.dynamic_rendering
+            .as_ref()
+            .expect("Dynamic rendering is not enabled on this device");
+
+        unsafe {
+            dynamic_rendering.cmd_end_rendering(cb);
+        }
+    }
+}
+
+// Has to be chained into vk::GraphicsPipelineCreateInfo, which then uses a null render pass.
+pub fn get_pipeline_rendering_create_info<'a>(
+    desc: &'a RenderingFormatsDesc,
+) -> vk::PipelineRenderingCreateInfoBuilder<'a> {
+    let mut create_info = vk::PipelineRenderingCreateInfo::builder()
+        .color_attachment_formats(desc.color_formats)
+        .view_mask(desc.view_mask);
+
+    if let Some(depth_format) = desc.depth_format {
+        create_info = create_info.depth_attachment_format(depth_format);
+        if image_aspect_mask_from_format(depth_format).contains(vk::ImageAspectFlags::STENCIL) {
+            create_info = create_info.stencil_attachment_format(depth_format);
+        }
+    }
+    create_info
+}
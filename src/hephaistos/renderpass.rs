@@ -1,8 +1,6 @@
 
 use super::*;
-use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
-use raw_window_handle::HasRawWindowHandle;
+use ash::vk;
 use std::sync::Arc;
 
 pub trait CreateRenderPass{
@@ -11,45 +9,151 @@ pub trait CreateRenderPass{
 
 impl CreateRenderPass for Arc<SharedDevice>{
     fn create_render_pass(&self, desc: &RenderPassDesc<'_>) -> Arc<RenderPass> {
+        assert!(
+            desc.resolve_attachments.is_empty()
+                || desc.resolve_attachments.len() == desc.color_attachments.len(),
+            "RenderPassDesc needs either no resolve attachments or one per color attachment"
+        );
+
         let renderpass_attachments = desc
             .color_attachments
             .iter()
-            .map(|a| {
-                *a
+            .copied()
+            .chain(desc.depth_attachment.map(vk::AttachmentDescription::from))
+            .chain(desc.resolve_attachments.iter().copied())
+            .collect::<Vec<_>>();
+
+        let depth_attachment_index = desc.depth_attachment.map(|_| desc.color_attachments.len() as u32);
+        let resolve_attachment_base = (desc.color_attachments.len()
+            + desc.depth_attachment.iter().count()) as u32;
+
+        let default_color_attachments = (0..desc.color_attachments.len() as u32).collect::<Vec<_>>();
+        let default_resolve_attachments = (0..desc.resolve_attachments.len() as u32)
+            .map(|attachment| resolve_attachment_base + attachment)
+            .collect::<Vec<_>>();
+        let default_subpasses = [SubpassDesc{
+            color_attachments: &default_color_attachments,
+            resolve_attachments: &default_resolve_attachments,
+            depth_attachment: depth_attachment_index,
+            ..Default::default()
+        }];
+        let subpasses = match desc.subpasses.is_empty(){
+            true => &default_subpasses[..],
+            false => desc.subpasses,
+        };
+
+        let attachment_ref = |attachment: u32, layout: vk::ImageLayout| vk::AttachmentReference{
+            attachment,
+            layout,
+        };
+        let depth_layout = |subpass: &SubpassDesc| subpass_depth_layout(desc, subpass);
+
+        // The references have to stay alive until the render pass is created.
+        let subpass_refs = subpasses
+            .iter()
+            .map(|subpass| {
+                assert!(
+                    subpass.resolve_attachments.is_empty()
+                        || subpass.resolve_attachments.len() == subpass.color_attachments.len(),
+                    "SubpassDesc needs either no resolve attachments or one per color attachment"
+                );
+                let color_refs = subpass.color_attachments
+                    .iter()
+                    .map(|attachment| attachment_ref(*attachment, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                    .collect::<Vec<_>>();
+                // Reading depth that is also bound for depth testing has to use the same layout.
+                let input_refs = subpass.input_attachments
+                    .iter()
+                    .map(|attachment| attachment_ref(*attachment, match Some(*attachment){
+                        index if index == subpass.depth_attachment => depth_layout(subpass),
+                        index if index == depth_attachment_index => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                        _ => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    }))
+                    .collect::<Vec<_>>();
+                let resolve_refs = subpass.resolve_attachments
+                    .iter()
+                    .map(|attachment| attachment_ref(*attachment, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                    .collect::<Vec<_>>();
+                let depth_ref = subpass.depth_attachment
+                    .map(|attachment| attachment_ref(attachment, depth_layout(subpass)));
+                (color_refs, input_refs, resolve_refs, depth_ref)
             })
-            .chain(desc.depth_attachment.as_ref().map(|a| {
-                *a
-            }))
             .collect::<Vec<_>>();
 
-        let color_attachment_refs = (0..desc.color_attachments.len() as u32)
-            .map(|attachment| vk::AttachmentReference {
-                attachment,
-                layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                //layout: desc.color_attachments[attachment as usize].initial_layout,
+        let subpass_descriptions = subpasses
+            .iter()
+            .zip(subpass_refs.iter())
+            .map(|(subpass, (color_refs, input_refs, resolve_refs, depth_ref))| {
+                let mut subpass_description = vk::SubpassDescription::builder()
+                    .color_attachments(color_refs)
+                    .input_attachments(input_refs)
+                    .preserve_attachments(subpass.preserve_attachments)
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+
+                if !resolve_refs.is_empty() {
+                    subpass_description = subpass_description.resolve_attachments(resolve_refs);
+                }
+
+                if let Some(depth_ref) = depth_ref {
+                    subpass_description = subpass_description.depth_stencil_attachment(depth_ref);
+                }
+                subpass_description.build()
             })
             .collect::<Vec<_>>();
 
-        let depth_attachment_ref = vk::AttachmentReference {
-            attachment: desc.color_attachments.len() as u32,
-            layout: vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL,
-            //layout: desc.depth_attachment.unwrap().initial_layout,
-        };
+        let dependencies = desc.dependencies
+            .iter()
+            .map(|dependency| {
+                let mut src_stage_mask = stage_mask(dependency.prev_accesses);
+                if src_stage_mask.is_empty() {
+                    src_stage_mask = vk::PipelineStageFlags::TOP_OF_PIPE;
+                }
+                let mut dst_stage_mask = stage_mask(dependency.next_accesses);
+                if dst_stage_mask.is_empty() {
+                    dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+                }
+                vk::SubpassDependency{
+                    src_subpass: dependency.src_subpass,
+                    dst_subpass: dependency.dst_subpass,
+                    src_stage_mask,
+                    dst_stage_mask,
+                    src_access_mask: src_access_mask(dependency.prev_accesses),
+                    dst_access_mask: dst_access_mask(dependency.next_accesses),
+                    dependency_flags: match dependency.by_region{
+                        true => vk::DependencyFlags::BY_REGION,
+                        false => vk::DependencyFlags::empty(),
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
 
-        let mut subpass_description = vk::SubpassDescription::builder()
-            .color_attachments(&color_attachment_refs)
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+        assert!(
+            desc.view_masks.is_empty() || desc.view_masks.len() == subpasses.len(),
+            "RenderPassDesc needs either no view masks or one per subpass"
+        );
+        assert!(
+            desc.view_masks.is_empty() || self.multiview,
+            "RenderPassDesc has view masks but multiview is not supported by the device"
+        );
 
-        if desc.depth_attachment.is_some() {
-            subpass_description =
-                subpass_description.depth_stencil_attachment(&depth_attachment_ref);
+        let key = RenderPassCacheKey::new(desc, &renderpass_attachments, subpasses, &dependencies);
+        let mut render_passes = self.render_passes.lock().unwrap();
+        if let Some(render_pass) = render_passes.get(&key).and_then(Weak::upgrade) {
+            return render_pass;
         }
-        let subpass_description = subpass_description.build();
 
-        let subpasses = [subpass_description];
-        let render_pass_create_info = vk::RenderPassCreateInfo::builder()
+        let mut multiview_create_info = vk::RenderPassMultiviewCreateInfo::builder()
+            .view_masks(desc.view_masks)
+            .correlation_masks(desc.correlation_masks);
+
+        let mut render_pass_create_info = vk::RenderPassCreateInfo::builder()
             .attachments(&renderpass_attachments)
-            .subpasses(&subpasses);
+            .subpasses(&subpass_descriptions)
+            .dependencies(&dependencies);
+
+        if !desc.view_masks.is_empty() {
+            render_pass_create_info = render_pass_create_info.push_next(&mut multiview_create_info);
+        }
 
         let render_pass = unsafe {
             self.raw
@@ -57,38 +161,170 @@ impl CreateRenderPass for Arc<SharedDevice>{
                 .unwrap()
         };
 
-        Arc::new(RenderPass {
+        let render_pass = Arc::new(RenderPass {
             raw: render_pass,
             framebuffer_cache: FramebufferCache::new(
                 render_pass,
                 desc.color_attachments,
                 desc.depth_attachment,
+                desc.resolve_attachments,
+                !desc.view_masks.is_empty(),
             ),
+            color_attachments: desc.color_attachments.iter().copied().collect(),
+            depth_attachment: desc.depth_attachment,
+            resolve_attachments: desc.resolve_attachments.iter().copied().collect(),
+            subpass_count: subpasses.len() as u32,
+            subpass_color_attachment_counts: subpasses
+                .iter()
+                .map(|subpass| subpass.color_attachments.len() as u32)
+                .collect(),
+            subpass_samples: subpass_samples(&renderpass_attachments, subpasses),
+            subpass_depth_layouts: subpasses
+                .iter()
+                .map(|subpass| subpass.depth_attachment.map(|_| depth_layout(subpass)))
+                .collect(),
             device: self.clone(),
+        });
+
+        render_passes.retain(|_, render_pass| render_pass.strong_count() > 0);
+        render_passes.insert(key, Arc::downgrade(&render_pass));
+        render_pass
+    }
+}
+
+// Color and depth attachments of a subpass have to agree on the sample count.
+fn subpass_samples(attachments: &[vk::AttachmentDescription], subpasses: &[SubpassDesc]) -> Vec<vk::SampleCountFlags>{
+    subpasses
+        .iter()
+        .enumerate()
+        .map(|(index, subpass)| {
+            let mut samples = subpass.color_attachments
+                .iter()
+                .chain(subpass.depth_attachment.iter())
+                .filter(|attachment| **attachment != vk::ATTACHMENT_UNUSED)
+                .map(|attachment| attachments[*attachment as usize].samples);
+            let first = samples.next().unwrap_or(vk::SampleCountFlags::TYPE_1);
+            assert!(
+                samples.all(|samples| samples == first),
+                "Color and depth attachments of subpass {} have different sample counts",
+                index
+            );
+            first
         })
+        .collect()
+}
+
+fn subpass_depth_layout(desc: &RenderPassDesc, subpass: &SubpassDesc) -> vk::ImageLayout{
+    subpass.depth_layout
+        .or(desc.depth_attachment.map(|depth_attachment| depth_attachment.layout))
+        .unwrap_or(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+}
+
+impl RenderPassCacheKey{
+    // Ignores state Vulkan ignores, so equivalent descriptions share one render pass.
+    pub fn new(
+        desc: &RenderPassDesc,
+        attachments: &[vk::AttachmentDescription],
+        subpasses: &[SubpassDesc],
+        dependencies: &[vk::SubpassDependency],
+    ) -> Self{
+        let attachments = attachments
+            .iter()
+            .map(|attachment| {
+                let has_stencil = image_aspect_mask_from_format(attachment.format)
+                    .contains(vk::ImageAspectFlags::STENCIL);
+                RenderPassAttachmentKey{
+                    flags: attachment.flags,
+                    format: attachment.format,
+                    samples: attachment.samples,
+                    load_op: attachment.load_op,
+                    store_op: attachment.store_op,
+                    stencil_load_op: match has_stencil{
+                        true => attachment.stencil_load_op,
+                        false => vk::AttachmentLoadOp::DONT_CARE,
+                    },
+                    stencil_store_op: match has_stencil{
+                        true => attachment.stencil_store_op,
+                        false => vk::AttachmentStoreOp::DONT_CARE,
+                    },
+                    initial_layout: attachment.initial_layout,
+                    final_layout: attachment.final_layout,
+                }
+            })
+            .collect();
+
+        let subpasses = subpasses
+            .iter()
+            .map(|subpass| SubpassKey{
+                color_attachments: subpass.color_attachments.to_vec(),
+                input_attachments: subpass.input_attachments.to_vec(),
+                resolve_attachments: subpass.resolve_attachments.to_vec(),
+                depth_attachment: subpass.depth_attachment,
+                depth_layout: subpass_depth_layout(desc, subpass),
+                preserve_attachments: subpass.preserve_attachments.to_vec(),
+            })
+            .collect();
+
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| SubpassDependencyKey{
+                src_subpass: dependency.src_subpass,
+                dst_subpass: dependency.dst_subpass,
+                src_stage_mask: dependency.src_stage_mask,
+                dst_stage_mask: dependency.dst_stage_mask,
+                src_access_mask: dependency.src_access_mask,
+                dst_access_mask: dependency.dst_access_mask,
+                dependency_flags: dependency.dependency_flags,
+            })
+            .collect();
+
+        Self{
+            attachments,
+            color_attachment_count: desc.color_attachments.len(),
+            has_depth_attachment: desc.depth_attachment.is_some(),
+            subpasses,
+            dependencies,
+            view_masks: desc.view_masks.to_vec(),
+            correlation_masks: desc.correlation_masks.to_vec(),
+        }
     }
 }
 
 impl RenderPass{
     pub fn begin(&self, desc: &RenderPassBeginnDesc, draw_command_buffer: vk::CommandBuffer){
+        let area = desc.area.unwrap_or_else(|| vk::Rect2D{
+            offset: vk::Offset2D{x: 0, y: 0},
+            extent: self.default_extent(desc),
+        });
+        self.validate_begin_desc(desc, area);
+
+        let clear_values = desc.color_attachments
+            .iter()
+            .enumerate()
+            .map(|(i, _)| desc.color_clear_values.get(i).copied().unwrap_or_default().into())
+            .chain(desc.depth_attachment.map(|_| desc.depth_clear_value.unwrap_or_default().into()))
+            .collect::<ArrayVec<vk::ClearValue, MAX_ATTACHMENTS>>();
 
         //let framebuffer = self.framebuffer_cache.get_or_create(&self.device, key).unwrap();
-        let color_attachment_descs = desc.color_attachments.iter().map(|a|{
-            a.fb_attachment_desc
-        }).collect::<Vec<_>>();
         let framebuffer_key = FramebufferCacheKey::new(
-            desc.area.extent,
-            color_attachment_descs.iter(),
+            area.extent,
+            desc.color_attachments.iter().map(|a|{
+                &a.fb_attachment_desc
+            }),
             desc.depth_attachment.map(|a|{
                 &a.fb_attachment_desc
-            })
+            }),
+            desc.resolve_attachments.iter().map(|a|{
+                &a.fb_attachment_desc
+            }),
         );
 
         let image_attachments = desc.color_attachments.iter()
-            .chain(desc.depth_attachment.as_ref().into_iter())
+            .chain(desc.depth_attachment.as_ref())
+            .chain(desc.resolve_attachments.iter())
             .map(|v|{
                 v.raw
-            }).collect::<ArrayVec<vk::ImageView, MAX_COLOR_ATTACHMENTS>>();
+            }).collect::<ArrayVec<vk::ImageView, MAX_ATTACHMENTS>>();
 
         let mut pass_attachment_desc = vk::RenderPassAttachmentBeginInfoKHR::builder()
             .attachments(&image_attachments);
@@ -96,8 +332,8 @@ impl RenderPass{
         let render_pass_begin_info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.raw)
             .framebuffer(self.framebuffer_cache.get_or_create(&self.device, framebuffer_key).unwrap())
-            .render_area(desc.area)
-            .clear_values(desc.clear_values)
+            .render_area(area)
+            .clear_values(&clear_values)
             .push_next(&mut pass_attachment_desc);
 
         unsafe{
@@ -108,6 +344,118 @@ impl RenderPass{
             );
         }
     }
+    pub fn samples(&self, subpass: u32) -> vk::SampleCountFlags{
+        self.subpass_samples[subpass as usize]
+    }
+    // The largest area all attachments cover.
+    fn default_extent(&self, desc: &RenderPassBeginnDesc) -> vk::Extent2D{
+        desc.color_attachments
+            .iter()
+            .chain(desc.depth_attachment.as_ref())
+            .chain(desc.resolve_attachments.iter())
+            .map(|view| view_extent(view))
+            .fold(None, |extent: Option<vk::Extent2D>, view_extent| Some(match extent{
+                Some(extent) => vk::Extent2D{
+                    width: extent.width.min(view_extent.width),
+                    height: extent.height.min(view_extent.height),
+                },
+                None => view_extent,
+            }))
+            .expect("RenderPassBeginnDesc needs an area if it has no attachments")
+    }
+    fn validate_begin_desc(&self, desc: &RenderPassBeginnDesc, area: vk::Rect2D){
+        assert!(
+            desc.color_attachments.len() == self.color_attachments.len(),
+            "RenderPassBeginnDesc has {} color attachments but the render pass expects {}",
+            desc.color_attachments.len(),
+            self.color_attachments.len(),
+        );
+        assert!(
+            desc.depth_attachment.is_some() == self.depth_attachment.is_some(),
+            "RenderPassBeginnDesc depth attachment does not match the render pass",
+        );
+        assert!(
+            desc.resolve_attachments.len() == self.resolve_attachments.len(),
+            "RenderPassBeginnDesc has {} resolve attachments but the render pass expects {}",
+            desc.resolve_attachments.len(),
+            self.resolve_attachments.len(),
+        );
+        assert!(
+            desc.color_clear_values.is_empty()
+                || desc.color_clear_values.len() == desc.color_attachments.len(),
+            "RenderPassBeginnDesc needs either no color clear values or one per color attachment",
+        );
+
+        let attachments = desc.color_attachments
+            .iter()
+            .zip(self.color_attachments.iter().copied())
+            .map(|(view, attachment)| ("color", *view, attachment))
+            .chain(desc.depth_attachment.zip(self.depth_attachment).map(|(view, attachment)| {
+                ("depth", view, attachment.into())
+            }))
+            .chain(desc.resolve_attachments
+                .iter()
+                .zip(self.resolve_attachments.iter().copied())
+                .map(|(view, attachment)| ("resolve", *view, attachment)));
+
+        for (i, (kind, view, attachment)) in attachments.enumerate(){
+            let format = view.desc.format.unwrap_or(view.image_desc.format);
+            assert!(
+                format == attachment.format,
+                "RenderPassBeginnDesc {} attachment {} has format {:?} but the render pass expects {:?}",
+                kind, i, format, attachment.format,
+            );
+            assert!(
+                view.image_desc.sample_count == attachment.samples,
+                "RenderPassBeginnDesc {} attachment {} has {:?} samples but the render pass expects {:?}",
+                kind, i, view.image_desc.sample_count, attachment.samples,
+            );
+            let extent = view_extent(view);
+            assert!(
+                area.offset.x >= 0
+                    && area.offset.y >= 0
+                    && area.offset.x as u32 + area.extent.width <= extent.width
+                    && area.offset.y as u32 + area.extent.height <= extent.height,
+                "RenderPassBeginnDesc area {:?} exceeds the {}x{} extent of {} attachment {}",
+                area, extent.width, extent.height, kind, i,
+            );
+        }
+
+        for (i, attachment) in self.color_attachments.iter().enumerate(){
+            if attachment.load_op != vk::AttachmentLoadOp::CLEAR{
+                continue;
+            }
+            let clear_value = desc.color_clear_values.get(i);
+            assert!(
+                clear_value.is_some(),
+                "RenderPassBeginnDesc color attachment {} is cleared but has no clear value",
+                i,
+            );
+            let expected = clear_color_type(attachment.format);
+            assert!(
+                std::mem::discriminant(clear_value.unwrap()) == std::mem::discriminant(&expected),
+                "RenderPassBeginnDesc clear value {:?} does not match format {:?} of color attachment {}",
+                clear_value.unwrap(), attachment.format, i,
+            );
+        }
+        if let Some(attachment) = self.depth_attachment{
+            let clears = attachment.depth_load_op == vk::AttachmentLoadOp::CLEAR
+                || attachment.stencil_load_op == vk::AttachmentLoadOp::CLEAR;
+            assert!(
+                !clears || desc.depth_clear_value.is_some(),
+                "RenderPassBeginnDesc depth attachment is cleared but has no clear value",
+            );
+        }
+    }
+    pub fn next_subpass(&self, draw_command_buffer: vk::CommandBuffer){
+        unsafe{
+            self.device.cmd_next_subpass(draw_command_buffer, vk::SubpassContents::INLINE);
+        }
+    }
+    // Has to be called when attachments are recreated, e.g. after the swapchain was resized.
+    pub fn invalidate_framebuffers(&self){
+        self.framebuffer_cache.invalidate();
+    }
     pub fn end(&self, draw_command_buffer: vk::CommandBuffer){
         unsafe{
             self.device.cmd_end_render_pass(draw_command_buffer);
@@ -123,3 +471,81 @@ impl Drop for RenderPass{
         };
     }
 }
+
+// Extent of the mip level a view starts at.
+fn view_extent(view: &ImageView) -> vk::Extent2D{
+    vk::Extent2D{
+        width: (view.image_desc.extent.width >> view.desc.base_mip_level).max(1),
+        height: (view.image_desc.extent.height >> view.desc.base_mip_level).max(1),
+    }
+}
+
+// Integer formats have to be cleared with integer values of the same signedness.
+fn clear_color_type(format: vk::Format) -> ClearColor{
+    match format_numeric_type(format){
+        FormatNumericType::Uint => ClearColor::Uint([0; 4]),
+        FormatNumericType::Sint => ClearColor::Int([0; 4]),
+        FormatNumericType::Float => ClearColor::Float([0.0; 4]),
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn attachment(samples: vk::SampleCountFlags) -> vk::AttachmentDescription{
+        vk::AttachmentDescription{
+            samples,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn samples_come_from_the_subpass_attachments(){
+        // A multisampled color pass, a single sampled depth only pass and a resolve style pass.
+        let attachments = [
+            attachment(vk::SampleCountFlags::TYPE_4),
+            attachment(vk::SampleCountFlags::TYPE_4),
+            attachment(vk::SampleCountFlags::TYPE_1),
+            attachment(vk::SampleCountFlags::TYPE_1),
+        ];
+        let subpasses = [
+            SubpassDesc{
+                color_attachments: &[0],
+                depth_attachment: Some(1),
+                ..Default::default()
+            },
+            SubpassDesc{
+                depth_attachment: Some(2),
+                ..Default::default()
+            },
+            SubpassDesc{
+                color_attachments: &[vk::ATTACHMENT_UNUSED, 3],
+                input_attachments: &[0],
+                ..Default::default()
+            },
+            SubpassDesc::default(),
+        ];
+        assert_eq!(
+            subpass_samples(&attachments, &subpasses),
+            [
+                vk::SampleCountFlags::TYPE_4,
+                vk::SampleCountFlags::TYPE_1,
+                vk::SampleCountFlags::TYPE_1,
+                vk::SampleCountFlags::TYPE_1,
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "different sample counts")]
+    fn mismatched_subpass_samples_are_rejected(){
+        let attachments = [attachment(vk::SampleCountFlags::TYPE_4), attachment(vk::SampleCountFlags::TYPE_1)];
+        let subpasses = [SubpassDesc{
+            color_attachments: &[0],
+            depth_attachment: Some(1),
+            ..Default::default()
+        }];
+        subpass_samples(&attachments, &subpasses);
+    }
+}
@@ -0,0 +1,48 @@
+use super::*;
+use ash::vk;
+
+impl SharedDevice{
+    // Creates a sampler the caller owns and has to destroy, most users want RenderDevice::get_sampler.
+    pub fn create_uncached_sampler(&self, desc: SamplerDesc) -> Sampler{
+        let max_anisotropy = desc.max_anisotropy
+            .filter(|_| self.features.sampler_anisotropy == vk::TRUE)
+            .map(|max_anisotropy| {
+                max_anisotropy.min(self.adapter.properties().limits.max_sampler_anisotropy)
+            });
+
+        let create_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(desc.mag_filter)
+            .min_filter(desc.min_filter)
+            .mipmap_mode(desc.mipmap_mode)
+            .address_mode_u(desc.address_mode_u)
+            .address_mode_v(desc.address_mode_v)
+            .address_mode_w(desc.address_mode_w)
+            .mip_lod_bias(desc.mip_lod_bias)
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1.0))
+            .compare_enable(desc.compare_op.is_some())
+            .compare_op(desc.compare_op.unwrap_or(vk::CompareOp::NEVER))
+            .min_lod(desc.min_lod)
+            .max_lod(desc.max_lod)
+            .border_color(desc.border_color)
+            .unnormalized_coordinates(desc.unnormalized_coordinates);
+
+        Sampler{
+            raw: unsafe{
+                self.raw
+                    .create_sampler(&create_info, None)
+                    .expect("Could not create sampler")
+            },
+            desc,
+        }
+    }
+}
+
+impl RenderDevice{
+    pub fn get_sampler(&self, desc: SamplerDesc) -> Sampler{
+        let mut samplers = self.samplers.lock().unwrap();
+        *samplers
+            .entry(desc)
+            .or_insert_with(|| self.shared.create_uncached_sampler(desc))
+    }
+}
@@ -0,0 +1,223 @@
+use super::*;
+use ash::vk;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+
+pub fn shader_stage_from_path(path: &Path) -> Option<vk::ShaderStageFlags>{
+    match path.extension()?.to_str()?{
+        "vert" => Some(vk::ShaderStageFlags::VERTEX),
+        "frag" => Some(vk::ShaderStageFlags::FRAGMENT),
+        "comp" => Some(vk::ShaderStageFlags::COMPUTE),
+        "geom" => Some(vk::ShaderStageFlags::GEOMETRY),
+        "tesc" => Some(vk::ShaderStageFlags::TESSELLATION_CONTROL),
+        "tese" => Some(vk::ShaderStageFlags::TESSELLATION_EVALUATION),
+        _ => None,
+    }
+}
+
+// GLSL is recognized by the glslang stage extensions or .glsl, HLSL by .hlsl. Anything else
+// (e.g. WGSL) is rejected when the ShaderSourceDesc is created.
+pub fn shader_source_language(path: &Path) -> Option<shaderc::SourceLanguage>{
+    match path.extension()?.to_str()?{
+        "hlsl" => Some(shaderc::SourceLanguage::HLSL),
+        "glsl" => Some(shaderc::SourceLanguage::GLSL),
+        _ => shader_stage_from_path(path).map(|_| shaderc::SourceLanguage::GLSL),
+    }
+}
+
+fn shader_kind(stage: vk::ShaderStageFlags) -> shaderc::ShaderKind{
+    match stage{
+        vk::ShaderStageFlags::VERTEX => shaderc::ShaderKind::Vertex,
+        vk::ShaderStageFlags::FRAGMENT => shaderc::ShaderKind::Fragment,
+        vk::ShaderStageFlags::COMPUTE => shaderc::ShaderKind::Compute,
+        vk::ShaderStageFlags::GEOMETRY => shaderc::ShaderKind::Geometry,
+        vk::ShaderStageFlags::TESSELLATION_CONTROL => shaderc::ShaderKind::TessControl,
+        vk::ShaderStageFlags::TESSELLATION_EVALUATION => shaderc::ShaderKind::TessEvaluation,
+        _ => panic!("Shader stage {:?} cannot be compiled at runtime", stage),
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime>{
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+pub struct CompiledShader{
+    pub stage: vk::ShaderStageFlags,
+    pub entry_point: CString,
+    pub code: Vec<u32>,
+    // The source file and everything it includes, with the modification time they were compiled at.
+    pub dependencies: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl CompiledShader{
+    pub fn stage_desc(&self) -> ShaderStageDesc<'_>{
+        ShaderStageDesc{
+            stage: self.stage,
+            code: &self.code,
+            entry_point: &self.entry_point,
+        }
+    }
+
+    pub fn is_outdated(&self) -> bool{
+        self.dependencies
+            .iter()
+            .any(|(path, modified)| modified_time(path) != *modified)
+    }
+}
+
+pub struct ShaderCompiler{
+    compiler: shaderc::Compiler,
+}
+
+impl Default for ShaderCompiler{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderCompiler{
+    pub fn new() -> Self{
+        Self{
+            compiler: shaderc::Compiler::new().expect("Could not create shader compiler"),
+        }
+    }
+
+    // Compile errors are returned as the compiler's message so callers can keep using the
+    // previous code; broken shaders are a normal part of editing them.
+    pub fn compile(&self, desc: &ShaderSourceDesc) -> Result<CompiledShader, String>{
+        let source_language = shader_source_language(&desc.path)
+            .ok_or_else(|| format!("{:?} is neither a GLSL nor an HLSL shader", desc.path))?;
+        let dependencies = RefCell::new(vec![(desc.path.clone(), modified_time(&desc.path))]);
+        let source = std::fs::read_to_string(&desc.path)
+            .map_err(|err| format!("Could not read {:?}: {}", desc.path, err))?;
+
+        let mut options = shaderc::CompileOptions::new().expect("Could not create shader compile options");
+        options.set_source_language(source_language);
+        if cfg!(debug_assertions){
+            options.set_generate_debug_info();
+        } else {
+            options.set_optimization_level(shaderc::OptimizationLevel::Performance);
+        }
+        // Both relative and <standard> includes are resolved next to the including file.
+        options.set_include_callback(|name, _include_type, source_name, _depth| {
+            let path = Path::new(source_name)
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(name);
+            let content = std::fs::read_to_string(&path)
+                .map_err(|err| format!("Could not read {:?}: {}", path, err))?;
+            dependencies.borrow_mut().push((path.clone(), modified_time(&path)));
+            Ok(shaderc::ResolvedInclude{
+                resolved_name: path.to_string_lossy().into_owned(),
+                content,
+            })
+        });
+
+        let artifact = self.compiler
+            .compile_into_spirv(
+                &source,
+                shader_kind(desc.stage),
+                &desc.path.to_string_lossy(),
+                desc.entry_point.to_str().expect("Entry point is not valid UTF-8"),
+                Some(&options),
+            )
+            .map_err(|err| err.to_string())?;
+        if artifact.get_num_warnings() > 0{
+            log::warn!("{}", artifact.get_warning_messages());
+        }
+        let code = artifact.as_binary().to_vec();
+        drop(options);
+
+        Ok(CompiledShader{
+            stage: desc.stage,
+            entry_point: desc.entry_point.clone(),
+            code,
+            dependencies: dependencies.into_inner(),
+        })
+    }
+}
+
+type CreatePipeline<'a> = Box<dyn Fn(&RenderDevice, &[ShaderStageDesc]) -> Pipeline + 'a>;
+
+// Pipeline that is rebuilt from its shader sources when they change on disk. Replaced
+// pipelines are destroyed once the frames that could still use them have finished.
+pub struct ReloadablePipeline<'a>{
+    compiler: ShaderCompiler,
+    sources: Vec<ShaderSourceDesc>,
+    shaders: Vec<CompiledShader>,
+    // Shaders whose latest source failed to compile, the pipeline is only rebuilt once none are left.
+    broken: Vec<bool>,
+    rebuild_pending: bool,
+    create: CreatePipeline<'a>,
+    pipeline: Pipeline,
+    retired: Vec<(u64, Pipeline)>,
+}
+
+impl<'a> ReloadablePipeline<'a>{
+    pub fn new(
+        device: &RenderDevice,
+        sources: &[ShaderSourceDesc],
+        create: impl Fn(&RenderDevice, &[ShaderStageDesc]) -> Pipeline + 'a,
+    ) -> Self{
+        let compiler = ShaderCompiler::new();
+        let shaders = sources
+            .iter()
+            .map(|source| compiler.compile(source).unwrap_or_else(|err| panic!("{}", err)))
+            .collect::<Vec<_>>();
+        let pipeline = create(device, &shaders.iter().map(CompiledShader::stage_desc).collect::<Vec<_>>());
+        Self{
+            compiler,
+            sources: sources.to_vec(),
+            broken: vec![false; shaders.len()],
+            shaders,
+            rebuild_pending: false,
+            create: Box::new(create),
+            pipeline,
+            retired: Vec::new(),
+        }
+    }
+
+    pub fn pipeline(&self) -> &Pipeline{
+        &self.pipeline
+    }
+
+    // Has to be called between frames, before the pipeline is recorded into the next one.
+    // Returns whether the pipeline was rebuilt.
+    pub fn reload(&mut self, device: &RenderDevice) -> bool{
+        let frame_index = device.frame_index.load(Ordering::Relaxed);
+        self.retired.retain(|(retired_frame, _)| *retired_frame + FRAMES_IN_FLIGHT as u64 > frame_index);
+
+        for ((source, shader), broken) in self.sources.iter().zip(self.shaders.iter_mut()).zip(self.broken.iter_mut()){
+            if !shader.is_outdated(){
+                continue;
+            }
+            match self.compiler.compile(source){
+                Ok(compiled) => {
+                    *shader = compiled;
+                    *broken = false;
+                    self.rebuild_pending = true;
+                }
+                Err(err) => {
+                    log::error!("{}", err);
+                    // Only retry once the file is saved again.
+                    for (path, modified) in shader.dependencies.iter_mut(){
+                        *modified = modified_time(path);
+                    }
+                    *broken = true;
+                }
+            }
+        }
+        if !self.rebuild_pending || self.broken.iter().any(|broken| *broken){
+            return false;
+        }
+        self.rebuild_pending = false;
+
+        let pipeline = (self.create)(device, &self.shaders.iter().map(CompiledShader::stage_desc).collect::<Vec<_>>());
+        let old_pipeline = std::mem::replace(&mut self.pipeline, pipeline);
+        self.retired.push((frame_index, old_pipeline));
+        true
+    }
+}
@@ -1,8 +1,7 @@
 
 use super::*;
 use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
-use raw_window_handle::HasRawWindowHandle;
+use ash::vk;
 use std::sync::Arc;
 
 pub trait SharedSurface{
@@ -47,7 +46,7 @@ impl SharedSurface for Arc<Surface>{
                 .cloned()
                 .find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
                 .unwrap_or(vk::PresentModeKHR::FIFO);
-            let swapchain_loader = khr::Swapchain::new(&self.instance.raw, &device);
+            let swapchain_loader = khr::Swapchain::new(&self.instance.raw, device);
 
             let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
                 .surface(self.raw)
@@ -68,14 +67,14 @@ impl SharedSurface for Arc<Surface>{
                 .unwrap();
 
             let images = swapchain_loader.get_swapchain_images(swapchain).unwrap();
-            let images: Vec<Arc<Image>> = images.into_iter().enumerate().map(|(i, vk_image)|{
+            let images: Vec<Arc<Image>> = images.into_iter().map(|vk_image|{
                 Arc::new(Image{
                     raw: vk_image,
                     desc: ImageDesc{
                         image_type: ImageType::Tex2d,
                         usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
                         flags: vk::ImageCreateFlags::empty(),
-                        format: vk::Format::B8G8R8A8_SRGB,
+                        format: surface_format.format,
                         extent: vk::Extent3D{
                             width: extent.width,
                             height: extent.height,
@@ -84,8 +83,11 @@ impl SharedSurface for Arc<Surface>{
                         tiling: vk::ImageTiling::OPTIMAL,
                         mip_levels: 1,
                         array_elements: 1,
+                        sample_count: vk::SampleCountFlags::TYPE_1,
                     },
                     views: Mutex::new(FxHashMap::default()),
+                    access: Mutex::new(vec![vk_sync::AccessType::Nothing]),
+                    allocation: None,
                     device: device.shared.clone(),
                 },
                 )
@@ -1,21 +1,17 @@
 
 use super::*;
-use ash::extensions::khr;
-use ash::{extensions::ext::DebugUtils, vk};
-use raw_window_handle::HasRawWindowHandle;
-use std::borrow::BorrowMut;
-use std::sync::Arc;
+use ash::vk;
 
 impl Swapchain{
     pub fn acquire_next_image(&self) -> Option<SwapchainImage>{
         unsafe{
-            let next_semaphore = self.next_semaphore.lock().unwrap().clone();
+            let next_semaphore = *self.next_semaphore.lock().unwrap();
             let acquire_semaphore = self.acquire_semaphores[next_semaphore];
             let rendering_finished_semaphore = self.rendering_finished_semaphores[next_semaphore];
 
             let present_index = self.loader.acquire_next_image(
                 self.raw, 
-                std::u64::MAX,
+                u64::MAX,
                 acquire_semaphore,
                 vk::Fence::null())
                 .map(|(val, _)| val as usize);
@@ -64,7 +60,7 @@ impl Swapchain{
 impl Drop for Swapchain{
     fn drop(&mut self) {
         unsafe{
-            println!("Swapchain::drop");
+            log::trace!("Swapchain::drop");
             self.acquire_semaphores.iter_mut().for_each(|s|{
                 self.device.destroy_semaphore(*s, None);
             });
@@ -1,10 +1,8 @@
 
-mod hephaistos;
-
-use hephaistos::*;
+use test04::hephaistos;
+use test04::hephaistos::*;
 
 use ash::util::*;
-use std::io::Cursor;
 use std::mem;
 use std::mem::align_of;
 use std::sync::Arc;
@@ -12,20 +10,12 @@ use std::sync::Arc;
 extern crate ash;
 extern crate winit;
 
-use ash::extensions::{
-    ext::DebugUtils,
-    khr::{Surface, Swapchain},
-};
-
-use ash::{vk, Entry};
+use ash::vk;
 pub use ash::{Device, Instance};
 pub use ash::extensions::{khr, ext};
-use std::borrow::Cow;
 use std::cell::RefCell;
 use std::default::Default;
-use std::ffi::CStr;
 use std::ops::Drop;
-use std::os::raw::c_char;
 
 use winit::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
@@ -53,10 +43,10 @@ pub fn record_submit_commandbuffer<F: FnOnce(&Device, vk::CommandBuffer)>(
     device: &Device,
     command_buffer: vk::CommandBuffer,
     //command_buffer_reuse_fence: vk::Fence,
-    submit_queue: vk::Queue,
-    wait_mask: &[vk::PipelineStageFlags],
-    wait_semaphores: &[vk::Semaphore],
-    signal_semaphores: &[vk::Semaphore],
+    _submit_queue: vk::Queue,
+    _wait_mask: &[vk::PipelineStageFlags],
+    _wait_semaphores: &[vk::Semaphore],
+    _signal_semaphores: &[vk::Semaphore],
     f: F,
 ) {
     unsafe {
@@ -142,7 +132,9 @@ pub struct ExampleBase {
     //pub draw_command_buffer: vk::CommandBuffer,
     //pub setup_command_buffer: vk::CommandBuffer,
 
-    pub depth_image: hephaistos::Image,
+    pub msaa_samples: vk::SampleCountFlags,
+    pub color_image_desc: ImageDesc,
+    pub depth_image_desc: ImageDesc,
     //pub depth_image_view: vk::ImageView,
     //pub depth_image_memory: vk::DeviceMemory,
 
@@ -178,177 +170,199 @@ impl ExampleBase {
     }
 
     pub fn new(window_width: u32, window_height: u32) -> Self {
-        unsafe {
-            let event_loop = EventLoop::new();
-            let window = WindowBuilder::new()
-                .with_title("Ash - Example")
-                .with_inner_size(winit::dpi::LogicalSize::new(
-                    f64::from(window_width),
-                    f64::from(window_height),
-                ))
-                .build(&event_loop)
-                .unwrap();
-
-            let instance = hephaistos::Instance::init(Some(&window));
-            let mut surface = instance.create_surface(&window);
-
-            let adapter = instance.request_adapter(&hephaistos::AdapterDesc{
-                compatible_surface: Some(&surface),
-                queue_flags: vk::QueueFlags::GRAPHICS,
-            });
+        let event_loop = EventLoop::new();
+        let window = WindowBuilder::new()
+            .with_title("Ash - Example")
+            .with_inner_size(winit::dpi::LogicalSize::new(
+                f64::from(window_width),
+                f64::from(window_height),
+            ))
+            .build(&event_loop)
+            .unwrap();
 
-            let device = adapter.request_device();
+        let instance = hephaistos::Instance::init(Some(&window));
+        let mut surface = instance.create_surface(&window);
 
-            //let swapchain = instance.create_swapchain(&adapter, &device, &surface);
-            surface.create_swapchain(&device, adapter.clone());
+        let adapter = instance.request_adapter(&hephaistos::AdapterDesc{
+            compatible_surface: Some(&surface),
+            queue_flags: vk::QueueFlags::GRAPHICS,
+        });
 
+        let device = adapter.request_device(&hephaistos::DeviceDesc{
+            pipeline_cache_path: Some("pipeline_cache.bin".into()),
+            ..Default::default()
+        });
 
-            // Create Depth image
+        //let swapchain = instance.create_swapchain(&adapter, &device, &surface);
+        surface.create_swapchain(&device, adapter.clone());
 
-            let depth_image = device.create_image(&ImageDesc{
-                image_type: ImageType::Tex2d,
-                usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-                format: vk::Format::D16_UNORM,
-                tiling: vk::ImageTiling::OPTIMAL,
-                extent: vk::Extent3D{
-                    width: surface.swapchain.as_ref().unwrap().extent.width,
-                    height: surface.swapchain.as_ref().unwrap().extent.height,
-                    depth: 1,
-                },
-                ..Default::default()
-            },Vec::new());
 
-            /*
-            // Internal
-            let pool_create_info = vk::CommandPoolCreateInfo::builder()
-                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-                .queue_family_index(adapter.queue_family_index);
-
-            let pool = device.create_command_pool(&pool_create_info, None).unwrap();
-
-            let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
-                .command_buffer_count(2)
-                .command_pool(pool)
-                .level(vk::CommandBufferLevel::PRIMARY);
-
-            let command_buffers = device
-                .allocate_command_buffers(&command_buffer_allocate_info)
-                .unwrap();
-            let setup_command_buffer = command_buffers[0];
-            let draw_command_buffer = command_buffers[1];
-
-            let device_memory_properties = instance.raw.get_physical_device_memory_properties(adapter.pdevice);
-            let depth_image_create_info = vk::ImageCreateInfo::builder()
-                .image_type(vk::ImageType::TYPE_2D)
-                .format(vk::Format::D16_UNORM)
-                .extent(surface.swapchain.as_ref().unwrap().extent.into())
-                .mip_levels(1)
-                .array_layers(1)
-                .samples(vk::SampleCountFlags::TYPE_1)
-                .tiling(vk::ImageTiling::OPTIMAL)
-                .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-                .sharing_mode(vk::SharingMode::EXCLUSIVE);
-
-            let depth_image = device.raw.create_image(&depth_image_create_info, None).unwrap();
-            let depth_image_memory_req = device.get_image_memory_requirements(depth_image);
-            let depth_image_memory_index = find_memorytype_index(
-                &depth_image_memory_req,
-                &device_memory_properties,
-                vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            )
-            .expect("Unable to find suitable memory index for depth image.");
-
-            let depth_image_allocate_info = vk::MemoryAllocateInfo::builder()
-                .allocation_size(depth_image_memory_req.size)
-                .memory_type_index(depth_image_memory_index);
-
-            let depth_image_memory = device
-                .allocate_memory(&depth_image_allocate_info, None)
-                .unwrap();
-
-            device
-                .bind_image_memory(depth_image, depth_image_memory, 0)
-                .expect("Unable to bind depth image memory");
-
-            let depth_image = device.create_image(&ImageDesc{
-                image_type: ImageType::Tex2d,
-                usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-                flags: vk::ImageCreateFlags::empty(),
-                format: vk::Format::D16_UNORM,
-                extent: surface.swapchain.as_ref().unwrap().extent.into(),
-                tiling: vk::ImageTiling::OPTIMAL,
-                mip_levels: 1,
-                array_elements: 1,
+        let msaa_samples = adapter.max_sample_count(vk::SampleCountFlags::TYPE_4);
+
+        // Multisampled color image, resolved into the swapchain image.
+
+        let color_image_desc = ImageDesc{
+            image_type: ImageType::Tex2d,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            format: surface.swapchain.as_ref().unwrap().surface_format.format,
+            tiling: vk::ImageTiling::OPTIMAL,
+            extent: vk::Extent3D{
+                width: surface.swapchain.as_ref().unwrap().extent.width,
+                height: surface.swapchain.as_ref().unwrap().extent.height,
+                depth: 1,
             },
-            Vec::new());
-
-            let fence_create_info =
-                vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
-
-            let draw_commands_reuse_fence = device
-                .create_fence(&fence_create_info, None)
-                .expect("Create fence failed.");
-            let setup_commands_reuse_fence = device
-                .create_fence(&fence_create_info, None)
-                .expect("Create fence failed.");
-
-            record_submit_commandbuffer(
-                &device,
-                setup_command_buffer,
-                setup_commands_reuse_fence,
-                device.global_queue,
-                &[],
-                &[],
-                &[],
-                |device, setup_command_buffer| {
-                    let layout_transition_barriers = vk::ImageMemoryBarrier::builder()
-                        .image(depth_image.raw)
-                        .dst_access_mask(
-                            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-                                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                        )
-                        .new_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-                        .old_layout(vk::ImageLayout::UNDEFINED)
-                        .subresource_range(
-                            vk::ImageSubresourceRange::builder()
-                                .aspect_mask(vk::ImageAspectFlags::DEPTH)
-                                .layer_count(1)
-                                .level_count(1)
-                                .build(),
-                        );
-
-                    device.cmd_pipeline_barrier(
-                        setup_command_buffer,
-                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                        vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
-                        vk::DependencyFlags::empty(),
-                        &[],
-                        &[],
-                        &[layout_transition_barriers.build()],
+            sample_count: msaa_samples,
+            ..Default::default()
+        };
+
+        // Depth image
+
+        let depth_image_desc = ImageDesc{
+            image_type: ImageType::Tex2d,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            format: adapter.best_depth_format(false),
+            tiling: vk::ImageTiling::OPTIMAL,
+            extent: vk::Extent3D{
+                width: surface.swapchain.as_ref().unwrap().extent.width,
+                height: surface.swapchain.as_ref().unwrap().extent.height,
+                depth: 1,
+            },
+            sample_count: msaa_samples,
+            ..Default::default()
+        };
+
+        /*
+        // Internal
+        let pool_create_info = vk::CommandPoolCreateInfo::builder()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(adapter.queue_family_index);
+
+        let pool = device.create_command_pool(&pool_create_info, None).unwrap();
+
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_buffer_count(2)
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::PRIMARY);
+
+        let command_buffers = device
+            .allocate_command_buffers(&command_buffer_allocate_info)
+            .unwrap();
+        let setup_command_buffer = command_buffers[0];
+        let draw_command_buffer = command_buffers[1];
+
+        let device_memory_properties = instance.raw.get_physical_device_memory_properties(adapter.pdevice);
+        let depth_image_create_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk::Format::D16_UNORM)
+            .extent(surface.swapchain.as_ref().unwrap().extent.into())
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let depth_image = device.raw.create_image(&depth_image_create_info, None).unwrap();
+        let depth_image_memory_req = device.get_image_memory_requirements(depth_image);
+        let depth_image_memory_index = find_memorytype_index(
+            &depth_image_memory_req,
+            &device_memory_properties,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .expect("Unable to find suitable memory index for depth image.");
+
+        let depth_image_allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(depth_image_memory_req.size)
+            .memory_type_index(depth_image_memory_index);
+
+        let depth_image_memory = device
+            .allocate_memory(&depth_image_allocate_info, None)
+            .unwrap();
+
+        device
+            .bind_image_memory(depth_image, depth_image_memory, 0)
+            .expect("Unable to bind depth image memory");
+
+        let depth_image = device.create_image(&ImageDesc{
+            image_type: ImageType::Tex2d,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            flags: vk::ImageCreateFlags::empty(),
+            format: vk::Format::D16_UNORM,
+            extent: surface.swapchain.as_ref().unwrap().extent.into(),
+            tiling: vk::ImageTiling::OPTIMAL,
+            mip_levels: 1,
+            array_elements: 1,
+        },
+        Vec::new());
+
+        let fence_create_info =
+            vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
+
+        let draw_commands_reuse_fence = device
+            .create_fence(&fence_create_info, None)
+            .expect("Create fence failed.");
+        let setup_commands_reuse_fence = device
+            .create_fence(&fence_create_info, None)
+            .expect("Create fence failed.");
+
+        record_submit_commandbuffer(
+            &device,
+            setup_command_buffer,
+            setup_commands_reuse_fence,
+            device.global_queue,
+            &[],
+            &[],
+            &[],
+            |device, setup_command_buffer| {
+                let layout_transition_barriers = vk::ImageMemoryBarrier::builder()
+                    .image(depth_image.raw)
+                    .dst_access_mask(
+                        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    )
+                    .new_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .subresource_range(
+                        vk::ImageSubresourceRange::builder()
+                            .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                            .layer_count(1)
+                            .level_count(1)
+                            .build(),
                     );
-                },
-            );
-            */
 
-            ExampleBase {
-                event_loop: RefCell::new(event_loop),
-                instance,
-                device,
-                adapter,
-                //device_memory_properties,
-                window,
-                //present_images,
-                //present_image_views,
-                //pool,
-                //draw_command_buffer,
-                //setup_command_buffer,
-                depth_image,
-                //depth_image_view,
-                //draw_commands_reuse_fence,
-                //setup_commands_reuse_fence,
-                surface,
-                //depth_image_memory,
-            }
+                device.cmd_pipeline_barrier(
+                    setup_command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[layout_transition_barriers.build()],
+                );
+            },
+        );
+        */
+
+        ExampleBase {
+            event_loop: RefCell::new(event_loop),
+            instance,
+            device,
+            adapter,
+            //device_memory_properties,
+            window,
+            //present_images,
+            //present_image_views,
+            //pool,
+            //draw_command_buffer,
+            //setup_command_buffer,
+            msaa_samples,
+            color_image_desc,
+            depth_image_desc,
+            //depth_image_view,
+            //draw_commands_reuse_fence,
+            //setup_commands_reuse_fence,
+            surface,
+            //depth_image_memory,
         }
     }
 }
@@ -380,7 +394,28 @@ struct Vertex {
     color: [f32; 4],
 }
 
+// Prints the library's log output, errors and warnings from validation and the hazard checker included.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
 fn main() {
+    log::set_logger(&LOGGER).expect("Could not set logger");
+    log::set_max_level(log::LevelFilter::Info);
     unsafe {
         let base = ExampleBase::new(800, 600);
 
@@ -388,22 +423,40 @@ fn main() {
             color_attachments: &[
                 vk::AttachmentDescription{
                     format: base.surface.swapchain.as_ref().unwrap().surface_format.format,
-                    samples: vk::SampleCountFlags::TYPE_1,
+                    samples: base.msaa_samples,
                     load_op: vk::AttachmentLoadOp::CLEAR,
-                    store_op: vk::AttachmentStoreOp::STORE,
-                    final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+                    store_op: vk::AttachmentStoreOp::DONT_CARE,
+                    initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
                     ..Default::default()
                 }
             ],
-            depth_attachment: Some(vk::AttachmentDescription{
-                format: vk::Format::D16_UNORM,
-                samples: vk::SampleCountFlags::TYPE_1,
-                load_op: vk::AttachmentLoadOp::CLEAR,
-                store_op: vk::AttachmentStoreOp::default(),
+            depth_attachment: Some(DepthStencilAttachmentDesc{
+                format: base.depth_image_desc.format,
+                samples: base.msaa_samples,
+                depth_load_op: vk::AttachmentLoadOp::CLEAR,
+                depth_store_op: vk::AttachmentStoreOp::default(),
                 initial_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
                 final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
                 ..Default::default()
-            })
+            }),
+            resolve_attachments: &[
+                vk::AttachmentDescription{
+                    format: base.surface.swapchain.as_ref().unwrap().surface_format.format,
+                    samples: vk::SampleCountFlags::TYPE_1,
+                    load_op: vk::AttachmentLoadOp::DONT_CARE,
+                    store_op: vk::AttachmentStoreOp::STORE,
+                    // The render graph transitions the swapchain image to PRESENT_SRC_KHR.
+                    initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    ..Default::default()
+                }
+            ],
+            subpasses: &[],
+            dependencies: &[],
+            view_masks: &[],
+            correlation_masks: &[],
         });
 
         let index_buffer_data = [0u32, 1, 2];
@@ -519,51 +572,6 @@ fn main() {
         base.device
             .bind_buffer_memory(vertex_input_buffer, vertex_input_buffer_memory, 0)
             .unwrap();
-        let mut vertex_spv_file =
-            Cursor::new(&include_bytes!("shaders/vert.spv")[..]);
-        let mut frag_spv_file = Cursor::new(&include_bytes!("shaders/frag.spv")[..]);
-
-        let vertex_code =
-            read_spv(&mut vertex_spv_file).expect("Failed to read vertex shader spv file");
-        let vertex_shader_info = vk::ShaderModuleCreateInfo::builder().code(&vertex_code);
-
-        let frag_code =
-            read_spv(&mut frag_spv_file).expect("Failed to read fragment shader spv file");
-        let frag_shader_info = vk::ShaderModuleCreateInfo::builder().code(&frag_code);
-
-        let vertex_shader_module = base
-            .device
-            .create_shader_module(&vertex_shader_info, None)
-            .expect("Vertex shader module error");
-
-        let fragment_shader_module = base
-            .device
-            .create_shader_module(&frag_shader_info, None)
-            .expect("Fragment shader module error");
-
-        let layout_create_info = vk::PipelineLayoutCreateInfo::default();
-
-        let pipeline_layout = base
-            .device
-            .create_pipeline_layout(&layout_create_info, None)
-            .unwrap();
-
-        let shader_entry_name = CStr::from_bytes_with_nul_unchecked(b"main\0");
-        let shader_stage_create_infos = [
-            vk::PipelineShaderStageCreateInfo {
-                module: vertex_shader_module,
-                p_name: shader_entry_name.as_ptr(),
-                stage: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            },
-            vk::PipelineShaderStageCreateInfo {
-                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-                module: fragment_shader_module,
-                p_name: shader_entry_name.as_ptr(),
-                stage: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            },
-        ];
         let vertex_input_binding_descriptions = [vk::VertexInputBindingDescription {
             binding: 0,
             stride: mem::size_of::<Vertex>() as u32,
@@ -584,13 +592,6 @@ fn main() {
             },
         ];
 
-        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_attribute_descriptions(&vertex_input_attribute_descriptions)
-            .vertex_binding_descriptions(&vertex_input_binding_descriptions);
-        let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-            ..Default::default()
-        };
         let viewports = [vk::Viewport {
             x: 0.0,
             y: 0.0,
@@ -600,191 +601,125 @@ fn main() {
             max_depth: 1.0,
         }];
         let scissors = [base.surface.swapchain.as_ref().unwrap().extent.into()];
-        let viewport_state_info = vk::PipelineViewportStateCreateInfo::builder()
-            .scissors(&scissors)
-            .viewports(&viewports);
-
-        let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
-            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
-            line_width: 1.0,
-            polygon_mode: vk::PolygonMode::FILL,
-            ..Default::default()
-        };
-        let multisample_state_info = vk::PipelineMultisampleStateCreateInfo {
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
-            ..Default::default()
-        };
-        let noop_stencil_state = vk::StencilOpState {
-            fail_op: vk::StencilOp::KEEP,
-            pass_op: vk::StencilOp::KEEP,
-            depth_fail_op: vk::StencilOp::KEEP,
-            compare_op: vk::CompareOp::ALWAYS,
-            ..Default::default()
-        };
-        let depth_state_info = vk::PipelineDepthStencilStateCreateInfo {
-            depth_test_enable: 1,
-            depth_write_enable: 1,
-            depth_compare_op: vk::CompareOp::LESS_OR_EQUAL,
-            front: noop_stencil_state,
-            back: noop_stencil_state,
-            max_depth_bounds: 1.0,
-            ..Default::default()
-        };
-        let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-            blend_enable: 0,
-            src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
-            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
-            color_blend_op: vk::BlendOp::ADD,
-            src_alpha_blend_factor: vk::BlendFactor::ZERO,
-            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-            alpha_blend_op: vk::BlendOp::ADD,
-            color_write_mask: vk::ColorComponentFlags::RGBA,
-        }];
-        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
-            .logic_op(vk::LogicOp::CLEAR)
-            .attachments(&color_blend_attachment_states);
-
-        let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-        let dynamic_state_info =
-            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_state);
-
-        let graphic_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(&shader_stage_create_infos)
-            .vertex_input_state(&vertex_input_state_info)
-            .input_assembly_state(&vertex_input_assembly_state_info)
-            .viewport_state(&viewport_state_info)
-            .rasterization_state(&rasterization_info)
-            .multisample_state(&multisample_state_info)
-            .depth_stencil_state(&depth_state_info)
-            .color_blend_state(&color_blend_state)
-            .dynamic_state(&dynamic_state_info)
-            .layout(pipeline_layout)
-            .render_pass(rpass.raw);
-
-        let graphics_pipelines = base
-            .device
-            .create_graphics_pipelines(
-                vk::PipelineCache::null(),
-                &[graphic_pipeline_info.build()],
-                None,
-            )
-            .expect("Unable to create graphics pipeline");
 
-        let graphic_pipeline = graphics_pipelines[0];
+        // Saving either shader while the demo runs rebuilds the pipeline.
+        let graphic_pipeline = RefCell::new(ReloadablePipeline::new(
+            &base.device,
+            &[
+                ShaderSourceDesc::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/triangle.vert")),
+                ShaderSourceDesc::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/triangle.frag")),
+            ],
+            |device, shaders| device.create_graphics_pipeline(
+                &GraphicsPipelineDesc {
+                    shaders,
+                    vertex_bindings: &vertex_input_binding_descriptions,
+                    vertex_attributes: &vertex_input_attribute_descriptions,
+                    ..Default::default()
+                },
+                PipelineTarget::RenderPass(&rpass),
+            ),
+        ));
 
         base.render_loop(|| {
+            graphic_pipeline.borrow_mut().reload(&base.device);
+            let graphic_pipeline = graphic_pipeline.borrow();
+
             let present_image = base.surface.acquire_next_image().unwrap();
-            let present_image_view = present_image.view(ImageViewDesc{
-                base_mip_level: 0,
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                level_count: Some(1),
-                ..Default::default()
-            });
-            let depth_image_view = base.depth_image.view(ImageViewDesc{
-                aspect_mask: vk::ImageAspectFlags::DEPTH,
-                base_mip_level: 0,
-                level_count: Some(1),
-                ..Default::default()
-            });
-            let clear_values = [
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 0.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
+            let frame = base.device.begin_frame();
+
+            let mut graph = RenderGraph::new();
+            let color_image = graph.create_image(base.color_image_desc);
+            let depth_image = graph.create_image(base.depth_image_desc);
+            let backbuffer = graph.import_swapchain_image(&present_image);
+
+            let mut pass = graph.add_pass("triangle");
+            pass.write_image(color_image, vk_sync::AccessType::ColorAttachmentWrite)
+                .write_image(depth_image, vk_sync::AccessType::DepthStencilAttachmentWrite)
+                .write_image(backbuffer, vk_sync::AccessType::ColorAttachmentWrite);
+            pass.render(|ctx| {
+                let device = &ctx.device;
+                let draw_command_buffer = ctx.cb;
+
+                let present_image_view = ctx.image(backbuffer).view(ImageViewDesc{
+                    base_mip_level: 0,
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    level_count: Some(1),
+                    ..Default::default()
+                }).unwrap();
+                let color_image_view = ctx.image(color_image).view(ImageViewDesc{
+                    base_mip_level: 0,
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    level_count: Some(1),
+                    ..Default::default()
+                }).unwrap();
+                let depth_image_view = ctx.image(depth_image).view(ImageViewDesc{
+                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    base_mip_level: 0,
+                    level_count: Some(1),
+                    ..Default::default()
+                }).unwrap();
+
+                rpass.begin(&RenderPassBeginnDesc{
+                    color_attachments: &[
+                        &color_image_view,
+                    ],
+                    depth_attachment: Some(&depth_image_view),
+                    resolve_attachments: &[
+                        &present_image_view,
+                    ],
+                    area: None,
+                    color_clear_values: &[ClearColor::Float([0.0, 0.0, 0.0, 0.0])],
+                    depth_clear_value: Some(ClearDepthStencil{
                         depth: 1.0,
                         stencil: 0,
-                    },
+                    }),
                 },
-            ];
-
-            let frame = base.device.begin_frame();
-
-            record_submit_commandbuffer(
-                &base.device,
-                frame.main_cb.raw,
-                //base.draw_commands_reuse_fence,
-                base.device.global_queue,
-                &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT],
-                &[present_image.acquire_semaphore],
-                &[present_image.rendering_finished_semaphore],
-                |device, draw_command_buffer| {
-                    rpass.begin(&RenderPassBeginnDesc{
-                        color_attachments: &[
-                            &present_image_view,
-                        ],
-                        depth_attachment: Some(&depth_image_view),
-                        area: vk::Rect2D{
-                            offset: vk::Offset2D{
-                                x: 0,
-                                y: 0
-                            },
-                            extent: vk::Extent2D{
-                                width: base.surface.swapchain.as_ref().unwrap().extent.width,
-                                height: base.surface.swapchain.as_ref().unwrap().extent.height,
-                            }
-                        },
-                        clear_values: &clear_values,
-                    },
+                draw_command_buffer,
+                );
+                graphic_pipeline.pipeline().bind(draw_command_buffer);
+                device.cmd_set_viewport(draw_command_buffer, 0, &viewports);
+                device.cmd_set_scissor(draw_command_buffer, 0, &scissors);
+                device.cmd_bind_vertex_buffers(
                     draw_command_buffer,
-                    );
-                    device.cmd_bind_pipeline(
-                        draw_command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        graphic_pipeline,
-                    );
-                    device.cmd_set_viewport(draw_command_buffer, 0, &viewports);
-                    device.cmd_set_scissor(draw_command_buffer, 0, &scissors);
-                    device.cmd_bind_vertex_buffers(
-                        draw_command_buffer,
-                        0,
-                        &[vertex_input_buffer],
-                        &[0],
-                    );
-                    device.cmd_bind_index_buffer(
-                        draw_command_buffer,
-                        index_buffer,
-                        0,
-                        vk::IndexType::UINT32,
-                    );
-                    device.cmd_draw_indexed(
-                        draw_command_buffer,
-                        index_buffer_data.len() as u32,
-                        1,
-                        0,
-                        0,
-                        1,
-                    );
-                    // Or draw without the index buffer
-                    // device.cmd_draw(draw_command_buffer, 3, 1, 0, 0);
-                    device.cmd_end_render_pass(draw_command_buffer);
-                },
+                    0,
+                    &[vertex_input_buffer],
+                    &[0],
+                );
+                device.cmd_bind_index_buffer(
+                    draw_command_buffer,
+                    index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(
+                    draw_command_buffer,
+                    index_buffer_data.len() as u32,
+                    1,
+                    0,
+                    0,
+                    1,
                 );
+                // Or draw without the index buffer
+                // device.cmd_draw(draw_command_buffer, 3, 1, 0, 0);
+                rpass.end(draw_command_buffer);
+            });
+            graph.export_image(backbuffer, vk_sync::AccessType::Present);
 
-            let submit_info = vk::SubmitInfo::builder()
-                .wait_semaphores(&[present_image.acquire_semaphore])
-                .signal_semaphores(&[present_image.rendering_finished_semaphore])
-                .wait_dst_stage_mask(&[])
-                .command_buffers(&[frame.main_cb.raw])
-                .build();
+            graph.execute(&base.device, &frame);
 
-            base.device.submit_frame(&[submit_info], &frame);
+            base.device.submit_command_buffer(
+                frame.main_cb.raw,
+                &[(present_image.acquire_semaphore, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)],
+                &[present_image.rendering_finished_semaphore],
+                frame.main_cb.submit_done_fence,
+            );
 
             base.surface.present_image(present_image);
             base.device.finish_frame(frame);
         });
 
         base.device.device_wait_idle().unwrap();
-        for pipeline in graphics_pipelines {
-            base.device.destroy_pipeline(pipeline, None);
-        }
-        base.device.destroy_pipeline_layout(pipeline_layout, None);
-        base.device
-            .destroy_shader_module(vertex_shader_module, None);
-        base.device
-            .destroy_shader_module(fragment_shader_module, None);
+        drop(graphic_pipeline);
         base.device.free_memory(index_buffer_memory, None);
         base.device.destroy_buffer(index_buffer, None);
         base.device.free_memory(vertex_input_buffer_memory, None);